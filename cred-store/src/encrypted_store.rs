@@ -0,0 +1,258 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use argon2::Argon2;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fs_util::{write_atomic, FileLock},
+    CredStore, Tokens,
+};
+
+/// Environment variable with the passphrase of the credentials file.
+pub const PASSPHRASE_ENV: &str = "MAMORU_CRED_PASSPHRASE";
+
+const ENVELOPE_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+/// Encrypted credentials as stored in the file.
+///
+/// The key is derived from the passphrase with Argon2id and a random salt, the JSON of the
+/// tokens is encrypted with XChaCha20-Poly1305. Salt and nonce change on every save.
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    kdf: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Credentials file encrypted with a passphrase.
+///
+/// A plaintext file written by [`crate::Credentials`] at the same path is encrypted the first
+/// time it's loaded.
+#[derive(Clone)]
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    passphrase: String,
+}
+
+impl EncryptedFileStore {
+    pub fn new(path: impl Into<PathBuf>, passphrase: impl Into<String>) -> Self {
+        EncryptedFileStore {
+            path: path.into(),
+            passphrase: passphrase.into(),
+        }
+    }
+
+    /// Passphrase from [`PASSPHRASE_ENV`], if set and not empty.
+    pub fn passphrase_from_env() -> Option<String> {
+        std::env::var(PASSPHRASE_ENV)
+            .ok()
+            .filter(|passphrase| !passphrase.is_empty())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn encrypt(&self, tokens: &Tokens) -> Result<Envelope, Error> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = XChaCha20Poly1305::new(&derive_key(&self.passphrase, &salt)?.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = tokens.to_json()?;
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| Error::other("couldn't encrypt credentials"))?;
+
+        Ok(Envelope {
+            version: ENVELOPE_VERSION,
+            kdf: "argon2id".to_string(),
+            salt: STANDARD.encode(salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+
+    fn decrypt(&self, envelope: &Envelope) -> Result<Tokens, Error> {
+        if envelope.version != ENVELOPE_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported credentials file version {}", envelope.version),
+            ));
+        }
+
+        let salt = decode(&envelope.salt)?;
+        let nonce = decode(&envelope.nonce)?;
+        if nonce.len() != 24 {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid nonce"));
+        }
+        let cipher = XChaCha20Poly1305::new(&derive_key(&self.passphrase, &salt)?.into());
+        let plaintext = cipher
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                decode(&envelope.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    "couldn't decrypt credentials, wrong passphrase?",
+                )
+            })?;
+
+        let plaintext =
+            String::from_utf8(plaintext).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        Tokens::from_json(&plaintext)
+    }
+}
+
+impl std::fmt::Debug for EncryptedFileStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedFileStore")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; KEY_LEN], Error> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::other(format!("couldn't derive key: {}", e)))?;
+    Ok(key)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, Error> {
+    STANDARD
+        .decode(value)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+#[async_trait]
+impl CredStore for EncryptedFileStore {
+    async fn load(&self) -> Result<Tokens, Error> {
+        if !self.path.exists() {
+            return Ok(Tokens::default());
+        }
+
+        let contents = fs::read_to_string(&self.path)?;
+        if let Ok(envelope) = serde_json::from_str::<Envelope>(&contents) {
+            return self.decrypt(&envelope);
+        }
+
+        // plaintext file of the file store, encrypted in place
+        let tokens = Tokens::from_json(&contents)?;
+        self.save(&tokens).await?;
+        Ok(tokens)
+    }
+
+    async fn save(&self, tokens: &Tokens) -> Result<(), Error> {
+        let contents = serde_json::to_vec_pretty(&self.encrypt(tokens)?)?;
+        write_atomic(&self.path, &contents)
+    }
+
+    async fn delete(&self) -> Result<(), Error> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+
+    async fn update(
+        &self,
+        update: &mut (dyn for<'t> FnMut(&'t mut Tokens) + Send),
+    ) -> Result<(), Error> {
+        let _lock = FileLock::acquire(&self.path)?;
+        let mut tokens = self.load().await?;
+        update(&mut tokens);
+        self.save(&tokens).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Namespace, TokenSet};
+
+    #[tokio::test]
+    async fn test_encrypted_roundtrip() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let path = tmp_dir.path().join(".credentials");
+        let namespace = Namespace::new("default", "https://mamoru.ai");
+
+        let store = EncryptedFileStore::new(&path, "secret");
+        store
+            .set(
+                &namespace,
+                TokenSet {
+                    access: "token".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("token\""));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let loaded = EncryptedFileStore::new(&path, "secret")
+            .get(&namespace)
+            .await
+            .unwrap();
+        assert_eq!(loaded.unwrap().access, "token");
+
+        let err = EncryptedFileStore::new(&path, "wrong")
+            .load()
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        store.delete().await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_plaintext() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let path = tmp_dir.path().join(".credentials");
+        fs::write(
+            &path,
+            r#"{"access_token": "token", "refresh_token": "refresh"}"#,
+        )
+        .unwrap();
+        let namespace = Namespace::new("default", "https://mamoru.ai");
+
+        let store = EncryptedFileStore::new(&path, "secret");
+        assert!(store.migrate(&namespace).await.unwrap());
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(serde_json::from_str::<Envelope>(&contents).is_ok());
+        assert!(!contents.contains("refresh\""));
+
+        let tokens = EncryptedFileStore::new(&path, "secret")
+            .get(&namespace)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tokens.access, "token");
+        assert_eq!(tokens.refresh, Some("refresh".to_string()));
+    }
+}
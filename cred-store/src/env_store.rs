@@ -0,0 +1,90 @@
+use std::{env, io::Error};
+
+use async_trait::async_trait;
+
+use crate::{CredStore, InMemoryStore, Namespace, TokenSet, Tokens};
+
+/// Environment variable with the access token, see [`EnvStore`].
+pub const ACCESS_TOKEN_ENV: &str = "MAMORU_ACCESS_TOKEN";
+/// Environment variable with the refresh token, optional.
+pub const REFRESH_TOKEN_ENV: &str = "MAMORU_REFRESH_TOKEN";
+
+/// Tokens given in [`ACCESS_TOKEN_ENV`] and [`REFRESH_TOKEN_ENV`], for CI pipelines.
+///
+/// The environment can't be written back, so tokens saved later, like a refreshed access token,
+/// are only kept in memory for the rest of the invocation.
+#[derive(Debug, Clone)]
+pub struct EnvStore {
+    memory: InMemoryStore,
+}
+
+impl EnvStore {
+    /// Store holding `tokens` under `namespace`.
+    pub fn new(namespace: &Namespace, tokens: TokenSet) -> Self {
+        let mut all = Tokens::default();
+        all.set(namespace, tokens);
+        EnvStore {
+            memory: InMemoryStore::with_tokens(all),
+        }
+    }
+
+    /// Store with the tokens of the environment under `namespace`, `None` when
+    /// [`ACCESS_TOKEN_ENV`] isn't set or empty.
+    pub fn from_env(namespace: &Namespace) -> Option<Self> {
+        let access = non_empty_var(ACCESS_TOKEN_ENV)?;
+        Some(EnvStore::new(
+            namespace,
+            TokenSet {
+                access,
+                refresh: non_empty_var(REFRESH_TOKEN_ENV),
+                ..Default::default()
+            },
+        ))
+    }
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+#[async_trait]
+impl CredStore for EnvStore {
+    async fn load(&self) -> Result<Tokens, Error> {
+        self.memory.load().await
+    }
+
+    async fn save(&self, tokens: &Tokens) -> Result<(), Error> {
+        self.memory.save(tokens).await
+    }
+
+    async fn delete(&self) -> Result<(), Error> {
+        self.memory.delete().await
+    }
+
+    async fn update(
+        &self,
+        update: &mut (dyn for<'t> FnMut(&'t mut Tokens) + Send),
+    ) -> Result<(), Error> {
+        self.memory.update(update).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_env_store() {
+        let namespace = Namespace::new("ci", "https://mamoru.ai");
+        env::set_var(ACCESS_TOKEN_ENV, "access");
+        env::set_var(REFRESH_TOKEN_ENV, "");
+        let store = EnvStore::from_env(&namespace).unwrap();
+        env::remove_var(ACCESS_TOKEN_ENV);
+        env::remove_var(REFRESH_TOKEN_ENV);
+
+        let tokens = store.get(&namespace).await.unwrap().unwrap();
+        assert_eq!(tokens.access, "access");
+        assert_eq!(tokens.refresh, None);
+        assert!(EnvStore::from_env(&namespace).is_none());
+    }
+}
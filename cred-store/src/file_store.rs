@@ -1,21 +1,40 @@
-use std::{collections::HashMap, fs, io::Error, path::Path};
+use std::{
+    env, fs,
+    io::Error,
+    path::{Path, PathBuf},
+};
 
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::CredStore;
+use crate::{
+    fs_util::{write_atomic, FileLock},
+    CredStore, Tokens,
+};
 
 const CREDENTIALS_FILE: &str = "credentials.json";
+const MAMORU_DIR: &str = ".mamorurc";
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Environment variable with the Mamoru directory, used instead of `~/.mamorurc`.
+pub const HOME_ENV: &str = "MAMORU_HOME";
+
+/// Directory of the settings and credentials: [`HOME_ENV`] when set, otherwise `~/.mamorurc`.
+pub fn mamoru_dir() -> Option<PathBuf> {
+    env::var_os(HOME_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home_dir| home_dir.join(MAMORU_DIR)))
+}
+
+/// Tokens stored as JSON in a file readable only by its owner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Credentials {
-    data: HashMap<String, String>,
     file_name: String,
 }
 
 impl Credentials {
     pub fn new() -> Self {
         Credentials {
-            data: HashMap::new(),
             file_name: CREDENTIALS_FILE.to_string(),
         }
     }
@@ -25,9 +44,24 @@ impl Credentials {
         self
     }
 
+    /// Path of the file: `file_name` when it's absolute, otherwise relative to [`mamoru_dir`].
+    pub fn path(&self) -> Result<PathBuf, Error> {
+        let file_name = Path::new(&self.file_name);
+        if file_name.is_absolute() {
+            return Ok(file_name.to_path_buf());
+        }
+
+        match mamoru_dir() {
+            Some(path) => Ok(path.join(file_name)),
+            None => Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                "Home directory not found",
+            )),
+        }
+    }
+
     pub fn build(&self) -> Self {
         Credentials {
-            data: self.data.clone(),
             file_name: self.file_name.clone(),
         }
     }
@@ -39,106 +73,154 @@ impl Default for Credentials {
     }
 }
 
+#[async_trait]
 impl CredStore for Credentials {
-    fn add(&mut self, key: String, value: String) -> &mut Self {
-        self.data.insert(key, value);
-        self
-    }
-
-    fn get(&self, key: &str) -> Option<&String> {
-        self.data.get(key)
-    }
-
-    fn clear(&mut self) -> &mut Self {
-        self.data.clear();
-        self
-    }
-
-    fn keys_present(&self, keys: &[String]) -> bool {
-        keys.iter().all(|key| self.data.contains_key(key))
-    }
-
-    fn load(&self) -> Result<Self, Error> {
-        let store_path = match dirs::home_dir() {
-            Some(path) => path.join(self.file_name.clone()),
-            None => {
-                return Err(Error::new(
-                    std::io::ErrorKind::NotFound,
-                    "Home directory not found",
-                ))
-            }
-        };
+    async fn load(&self) -> Result<Tokens, Error> {
+        let store_path = self.path()?;
         if Path::new(&store_path).exists() {
             let contents = fs::read_to_string(&store_path)?;
-            let data: HashMap<String, String> = serde_json::from_str(&contents)?;
-            Ok(Credentials {
-                data,
-                file_name: self.file_name.clone(),
-            })
+            Tokens::from_json(&contents)
         } else {
-            Ok(Credentials {
-                data: HashMap::new(),
-                file_name: self.file_name.clone(),
-            })
+            Ok(Tokens::default())
         }
     }
 
-    fn save(&self) -> Result<(), Error> {
-        let store_path = match dirs::home_dir() {
-            Some(path) => path.join(self.file_name.clone()),
-            None => {
-                return Err(Error::new(
-                    std::io::ErrorKind::NotFound,
-                    "Home directory not found",
-                ))
-            }
-        };
-        let contents = serde_json::to_string_pretty(&self.data)?;
-        fs::write(store_path, contents)?;
-        Ok(())
+    async fn save(&self, tokens: &Tokens) -> Result<(), Error> {
+        let store_path = self.path()?;
+        write_atomic(&store_path, tokens.to_json()?.as_bytes())
     }
 
-    fn delete(&self) -> Result<(), Error> {
-        let store_path = match dirs::home_dir() {
-            Some(path) => path.join(self.file_name.clone()),
-            None => {
-                return Err(Error::new(
-                    std::io::ErrorKind::NotFound,
-                    "Home directory not found",
-                ))
-            }
-        };
+    async fn delete(&self) -> Result<(), Error> {
+        let store_path = self.path()?;
         if Path::new(&store_path).exists() {
             fs::remove_file(store_path)?;
         }
         Ok(())
     }
+
+    async fn update(
+        &self,
+        update: &mut (dyn for<'t> FnMut(&'t mut Tokens) + Send),
+    ) -> Result<(), Error> {
+        let _lock = FileLock::acquire(&self.path()?)?;
+        let mut tokens = self.load().await?;
+        update(&mut tokens);
+        self.save(&tokens).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{Namespace, TokenSet};
+
+    #[tokio::test]
+    async fn test_credentials() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let credentials = Credentials::new()
+            .set_file_name(
+                tmp_dir
+                    .path()
+                    .join(".test.json")
+                    .to_string_lossy()
+                    .to_string(),
+            )
+            .build();
+        let namespace = Namespace::new("default", "https://mamoru.ai");
+
+        credentials
+            .set(
+                &namespace,
+                TokenSet {
+                    access: "admin".to_string(),
+                    refresh: Some("12345".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("Failed to save credentials");
+
+        let tokens = credentials
+            .get(&namespace)
+            .await
+            .expect("Failed to load credentials")
+            .expect("Tokens not found");
+        assert_eq!(tokens.refresh, Some("12345".to_string()));
+
+        assert!(credentials.remove(&namespace).await.unwrap());
+        assert!(!credentials.remove(&namespace).await.unwrap());
+        credentials
+            .delete()
+            .await
+            .expect("Failed to delete credentials");
+    }
 
     #[test]
-    fn test_credentials() {
-        let mut credentials = Credentials::new()
-            .set_file_name(".mamorurc/.test.json".to_string())
-            .build()
-            .load()
-            .expect("Failed to load credentials");
-
-        credentials.add("username".to_string(), "admin".to_string());
-        credentials.add("password".to_string(), "12345".to_string());
+    fn test_relative_path() {
+        let credentials = Credentials::new();
+        assert_eq!(
+            credentials.path().unwrap(),
+            mamoru_dir().unwrap().join(CREDENTIALS_FILE)
+        );
+    }
 
-        credentials.save().expect("Failed to save credentials");
+    #[test]
+    fn test_absolute_path() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let path = tmp_dir.path().join(".credentials");
+        let credentials = Credentials::new().set_file_name(path.to_string_lossy().to_string());
+        assert_eq!(credentials.path().unwrap(), path);
+    }
 
-        match credentials.get("username") {
-            Some(value) => println!("Username: {}", value),
-            None => println!("Username not found"),
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_parallel_updates() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let credentials = Credentials::new().set_file_name(
+            tmp_dir
+                .path()
+                .join(".credentials")
+                .to_string_lossy()
+                .to_string(),
+        );
+
+        let tasks = (0..8).map(|i| {
+            let credentials = credentials.clone();
+            tokio::spawn(async move {
+                credentials
+                    .set(
+                        &Namespace::new(format!("profile{}", i), "audience"),
+                        TokenSet {
+                            access: format!("access{}", i),
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .unwrap();
+            })
+        });
+        for task in tasks.collect::<Vec<_>>() {
+            task.await.unwrap();
         }
 
-        assert!(credentials.keys_present(&["username".to_string(), "password".to_string()]));
-        credentials.clear();
-        credentials.delete().expect("Failed to delete credentials");
+        assert_eq!(credentials.load().await.unwrap().namespaces().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_legacy_file() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let path = tmp_dir.path().join(".credentials");
+        fs::write(
+            &path,
+            r#"{"access_token": "access", "refresh_token": "refresh"}"#,
+        )
+        .unwrap();
+        let credentials = Credentials::new().set_file_name(path.to_string_lossy().to_string());
+        let namespace = Namespace::new("default", "https://mamoru.ai");
+
+        assert!(credentials.migrate(&namespace).await.unwrap());
+        assert!(!credentials.migrate(&namespace).await.unwrap());
+        let tokens = credentials.get(&namespace).await.unwrap().unwrap();
+        assert_eq!(tokens.access, "access");
+        assert_eq!(tokens.refresh, Some("refresh".to_string()));
     }
 }
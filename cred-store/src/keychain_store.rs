@@ -0,0 +1,198 @@
+use std::{
+    io::{Error, ErrorKind},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+
+use crate::{CredStore, Tokens};
+
+/// Service the credentials are stored under in the OS keychain.
+pub const KEYCHAIN_SERVICE: &str = "mamorurs-cli";
+/// Keychain account holding the credentials.
+pub const KEYCHAIN_ACCOUNT: &str = "credentials";
+
+/// Secret entry of the OS keychain, replaced by [`MockKeychain`] where there's no keychain.
+pub trait Keychain: Send + Sync {
+    /// Returns `None` when the entry doesn't exist.
+    fn get(&self) -> Result<Option<String>, Error>;
+    fn set(&self, secret: &str) -> Result<(), Error>;
+    /// Deleting a missing entry isn't an error.
+    fn delete(&self) -> Result<(), Error>;
+}
+
+/// Entry of the platform keychain: Secret Service over D-Bus on Linux, Keychain on macOS and
+/// Credential Manager on Windows.
+#[cfg(feature = "keychain")]
+pub struct OsKeychain {
+    entry: keyring::Entry,
+}
+
+#[cfg(feature = "keychain")]
+impl OsKeychain {
+    pub fn new(service: &str, account: &str) -> Result<Self, Error> {
+        let entry = keyring::Entry::new(service, account).map_err(Error::other)?;
+        Ok(OsKeychain { entry })
+    }
+}
+
+#[cfg(feature = "keychain")]
+impl Keychain for OsKeychain {
+    fn get(&self) -> Result<Option<String>, Error> {
+        match self.entry.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(Error::other(e)),
+        }
+    }
+
+    fn set(&self, secret: &str) -> Result<(), Error> {
+        self.entry.set_password(secret).map_err(Error::other)
+    }
+
+    fn delete(&self) -> Result<(), Error> {
+        match self.entry.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(Error::other(e)),
+        }
+    }
+}
+
+/// In-memory keychain for tests and headless CI without a Secret Service.
+///
+/// Clones share the same entry, like two handles of a real keychain.
+#[derive(Debug, Clone, Default)]
+pub struct MockKeychain {
+    secret: Arc<Mutex<Option<String>>>,
+    unavailable: bool,
+}
+
+impl MockKeychain {
+    pub fn new() -> Self {
+        MockKeychain::default()
+    }
+
+    /// Keychain failing every call, like a Linux session without D-Bus.
+    pub fn unavailable() -> Self {
+        MockKeychain {
+            unavailable: true,
+            ..Default::default()
+        }
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        if self.unavailable {
+            return Err(Error::new(ErrorKind::NotConnected, "keychain unavailable"));
+        }
+        Ok(())
+    }
+}
+
+impl Keychain for MockKeychain {
+    fn get(&self) -> Result<Option<String>, Error> {
+        self.check()?;
+        Ok(self.secret.lock().unwrap().clone())
+    }
+
+    fn set(&self, secret: &str) -> Result<(), Error> {
+        self.check()?;
+        *self.secret.lock().unwrap() = Some(secret.to_string());
+        Ok(())
+    }
+
+    fn delete(&self) -> Result<(), Error> {
+        self.check()?;
+        *self.secret.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+/// Tokens stored as a single JSON entry of a [`Keychain`].
+#[derive(Clone)]
+pub struct KeychainStore {
+    keychain: Arc<dyn Keychain>,
+}
+
+impl KeychainStore {
+    pub fn new(keychain: impl Keychain + 'static) -> Self {
+        KeychainStore {
+            keychain: Arc::new(keychain),
+        }
+    }
+
+    /// Store in the OS keychain under [`KEYCHAIN_SERVICE`] and [`KEYCHAIN_ACCOUNT`].
+    #[cfg(feature = "keychain")]
+    pub fn os() -> Result<Self, Error> {
+        Ok(KeychainStore::new(OsKeychain::new(
+            KEYCHAIN_SERVICE,
+            KEYCHAIN_ACCOUNT,
+        )?))
+    }
+}
+
+impl std::fmt::Debug for KeychainStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeychainStore").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl CredStore for KeychainStore {
+    async fn load(&self) -> Result<Tokens, Error> {
+        match self.keychain.get()? {
+            Some(contents) => Tokens::from_json(&contents),
+            None => Ok(Tokens::default()),
+        }
+    }
+
+    async fn save(&self, tokens: &Tokens) -> Result<(), Error> {
+        self.keychain.set(&tokens.to_json()?)
+    }
+
+    async fn delete(&self) -> Result<(), Error> {
+        self.keychain.delete()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Namespace, TokenSet};
+
+    #[tokio::test]
+    async fn test_keychain_store() {
+        let keychain = MockKeychain::new();
+        let namespace = Namespace::new("default", "https://mamoru.ai");
+        let store = KeychainStore::new(keychain.clone());
+        assert!(store.get(&namespace).await.unwrap().is_none());
+
+        store
+            .set(
+                &namespace,
+                TokenSet {
+                    access: "token".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let loaded = KeychainStore::new(keychain.clone());
+        assert_eq!(
+            loaded.get(&namespace).await.unwrap().unwrap().access,
+            "token"
+        );
+
+        loaded.delete().await.unwrap();
+        assert_eq!(keychain.get().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_keychain() {
+        let store = KeychainStore::new(MockKeychain::unavailable());
+        assert_eq!(
+            store.load().await.unwrap_err().kind(),
+            std::io::ErrorKind::NotConnected
+        );
+    }
+}
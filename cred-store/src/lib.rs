@@ -1,5 +1,18 @@
+pub mod encrypted_store;
+pub mod env_store;
 pub mod file_store;
+mod fs_util;
+pub mod keychain_store;
+pub mod memory_store;
+pub mod store;
+pub mod tokens;
 pub mod traits;
 
+pub use encrypted_store::{EncryptedFileStore, PASSPHRASE_ENV};
+pub use env_store::{EnvStore, ACCESS_TOKEN_ENV, REFRESH_TOKEN_ENV};
 pub use file_store::*;
+pub use keychain_store::*;
+pub use memory_store::InMemoryStore;
+pub use store::{Fallback, Store};
+pub use tokens::{Namespace, TokenSet, Tokens};
 pub use traits::CredStore;
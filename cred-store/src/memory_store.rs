@@ -0,0 +1,84 @@
+use std::{
+    io::Error,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+
+use crate::{CredStore, Tokens};
+
+/// Tokens kept in memory only, for tests and automation without a writable home directory.
+///
+/// Clones share the same tokens.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStore {
+    tokens: Arc<Mutex<Tokens>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        InMemoryStore::default()
+    }
+
+    pub fn with_tokens(tokens: Tokens) -> Self {
+        InMemoryStore {
+            tokens: Arc::new(Mutex::new(tokens)),
+        }
+    }
+}
+
+#[async_trait]
+impl CredStore for InMemoryStore {
+    async fn load(&self) -> Result<Tokens, Error> {
+        Ok(self.tokens.lock().unwrap().clone())
+    }
+
+    async fn save(&self, tokens: &Tokens) -> Result<(), Error> {
+        *self.tokens.lock().unwrap() = tokens.clone();
+        Ok(())
+    }
+
+    async fn delete(&self) -> Result<(), Error> {
+        *self.tokens.lock().unwrap() = Tokens::default();
+        Ok(())
+    }
+
+    async fn update(
+        &self,
+        update: &mut (dyn for<'t> FnMut(&'t mut Tokens) + Send),
+    ) -> Result<(), Error> {
+        update(&mut self.tokens.lock().unwrap());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Namespace, TokenSet};
+
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        let store = InMemoryStore::new();
+        let namespace = Namespace::new("default", "https://mamoru.ai");
+        store
+            .set(
+                &namespace,
+                TokenSet {
+                    access: "access".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let shared = store.clone();
+        assert_eq!(
+            shared.get(&namespace).await.unwrap().unwrap().access,
+            "access"
+        );
+
+        shared.delete().await.unwrap();
+        assert!(store.load().await.unwrap().is_empty());
+    }
+}
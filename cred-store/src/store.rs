@@ -0,0 +1,181 @@
+use std::io::{Error, ErrorKind};
+
+use async_trait::async_trait;
+
+use crate::{
+    CredStore, Credentials, EncryptedFileStore, EnvStore, InMemoryStore, KeychainStore, Tokens,
+};
+
+/// Credential store selected at runtime, see [`Store::open`].
+#[derive(Debug)]
+pub enum Store {
+    File(Credentials),
+    Keychain(KeychainStore),
+    Encrypted(EncryptedFileStore),
+    /// Tokens of the environment, used instead of the configured store when they're set.
+    Env(EnvStore),
+    Memory(InMemoryStore),
+}
+
+/// Store that replaced the requested one, with the reason.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fallback {
+    pub requested: String,
+    pub reason: String,
+}
+
+impl Fallback {
+    fn keychain(e: Error) -> Self {
+        Fallback {
+            requested: "keychain".to_string(),
+            reason: e.to_string(),
+        }
+    }
+}
+
+impl Store {
+    /// Opens the store named `kind`: `file` (the default), `keychain`, `encrypted` or `memory`,
+    /// which only keeps tokens for the lifetime of the process.
+    ///
+    /// When the keychain can't be used, because the crate was built without the `keychain`
+    /// feature or there's no keychain service running, the credentials are stored in `file`
+    /// and the reason is returned alongside.
+    ///
+    /// `encrypted` encrypts the file with the passphrase returned by `passphrase`, which is only
+    /// called for that store. The file is read once, so a wrong passphrase fails here.
+    pub async fn open(
+        kind: &str,
+        file: Credentials,
+        passphrase: impl FnOnce() -> Result<String, Error>,
+    ) -> Result<(Self, Option<Fallback>), Error> {
+        match kind {
+            "" | "file" => Ok((Store::File(file), None)),
+            "memory" => Ok((Store::Memory(InMemoryStore::new()), None)),
+            "encrypted" => {
+                let store = EncryptedFileStore::new(file.path()?, passphrase()?);
+                store.load().await?;
+                Ok((Store::Encrypted(store), None))
+            }
+            "keychain" => match os_keychain() {
+                Ok(keychain) => Store::open_keychain_or_file(keychain, file).await,
+                Err(e) => Ok((Store::File(file), Some(Fallback::keychain(e)))),
+            },
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "unknown credential store '{}', expected file, keychain, encrypted or memory",
+                    kind
+                ),
+            )),
+        }
+    }
+
+    /// Uses `keychain` when it can be read, `file` otherwise.
+    pub async fn open_keychain_or_file(
+        keychain: KeychainStore,
+        file: Credentials,
+    ) -> Result<(Self, Option<Fallback>), Error> {
+        match keychain.load().await {
+            Ok(_) => Ok((Store::Keychain(keychain), None)),
+            Err(e) => Ok((Store::File(file), Some(Fallback::keychain(e)))),
+        }
+    }
+
+    /// Name of the store, as accepted by [`Store::open`], or `env`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Store::File(_) => "file",
+            Store::Keychain(_) => "keychain",
+            Store::Encrypted(_) => "encrypted",
+            Store::Env(_) => "env",
+            Store::Memory(_) => "memory",
+        }
+    }
+
+    fn inner(&self) -> &dyn CredStore {
+        match self {
+            Store::File(store) => store,
+            Store::Keychain(store) => store,
+            Store::Encrypted(store) => store,
+            Store::Env(store) => store,
+            Store::Memory(store) => store,
+        }
+    }
+}
+
+#[cfg(feature = "keychain")]
+fn os_keychain() -> Result<KeychainStore, Error> {
+    KeychainStore::os()
+}
+
+#[cfg(not(feature = "keychain"))]
+fn os_keychain() -> Result<KeychainStore, Error> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "built without the keychain feature",
+    ))
+}
+
+#[async_trait]
+impl CredStore for Store {
+    async fn load(&self) -> Result<Tokens, Error> {
+        self.inner().load().await
+    }
+
+    async fn save(&self, tokens: &Tokens) -> Result<(), Error> {
+        self.inner().save(tokens).await
+    }
+
+    async fn delete(&self) -> Result<(), Error> {
+        self.inner().delete().await
+    }
+
+    async fn update(
+        &self,
+        update: &mut (dyn for<'t> FnMut(&'t mut Tokens) + Send),
+    ) -> Result<(), Error> {
+        self.inner().update(update).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockKeychain;
+
+    #[tokio::test]
+    async fn test_open_keychain_or_file() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let file = Credentials::new().set_file_name(
+            tmp_dir
+                .path()
+                .join(".test_store.json")
+                .to_string_lossy()
+                .to_string(),
+        );
+
+        let (store, fallback) =
+            Store::open_keychain_or_file(KeychainStore::new(MockKeychain::new()), file.build())
+                .await
+                .unwrap();
+        assert_eq!(store.kind(), "keychain");
+        assert_eq!(fallback, None);
+
+        let (store, fallback) = Store::open_keychain_or_file(
+            KeychainStore::new(MockKeychain::unavailable()),
+            file.build(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(store.kind(), "file");
+        assert_eq!(fallback.unwrap().requested, "keychain");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_store() {
+        let err = Store::open("vault", Credentials::new(), || Ok(String::new()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
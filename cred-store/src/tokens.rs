@@ -0,0 +1,196 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::Error,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Version of the format written by [`Tokens::to_json`].
+const TOKENS_VERSION: u32 = 2;
+
+/// Tokens received from the authorization server.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenSet {
+    pub access: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    /// Unix time in seconds the access token expires at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// OAuth grant the tokens were obtained with, when it isn't the interactive login.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grant: Option<String>,
+}
+
+impl TokenSet {
+    /// Whether `expires_at` is known and not after `now`.
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Profile and audience tokens are stored under, so logging in to another environment doesn't
+/// replace the tokens of the current one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Namespace {
+    pub profile: String,
+    pub audience: String,
+}
+
+impl Namespace {
+    pub fn new(profile: impl Into<String>, audience: impl Into<String>) -> Self {
+        Namespace {
+            profile: profile.into(),
+            audience: audience.into(),
+        }
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.profile, self.audience)
+    }
+}
+
+/// All tokens of a store, by profile and audience.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tokens {
+    version: u32,
+    #[serde(default)]
+    profiles: BTreeMap<String, BTreeMap<String, TokenSet>>,
+    /// Tokens read from the untyped `access_token`/`refresh_token` format, kept until
+    /// [`crate::CredStore::migrate`] moves them to a namespace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legacy: Option<TokenSet>,
+}
+
+impl Default for Tokens {
+    fn default() -> Self {
+        Tokens {
+            version: TOKENS_VERSION,
+            profiles: BTreeMap::new(),
+            legacy: None,
+        }
+    }
+}
+
+impl Tokens {
+    /// Parses the current format, or the string map written by previous versions.
+    pub fn from_json(contents: &str) -> Result<Self, Error> {
+        if let Ok(tokens) = serde_json::from_str::<Tokens>(contents) {
+            return Ok(tokens);
+        }
+
+        let mut legacy: HashMap<String, String> = serde_json::from_str(contents)?;
+        Ok(Tokens {
+            legacy: legacy.remove("access_token").map(|access| TokenSet {
+                access,
+                refresh: legacy
+                    .remove("refresh_token")
+                    .filter(|refresh| !refresh.is_empty()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn get(&self, namespace: &Namespace) -> Option<&TokenSet> {
+        self.profiles
+            .get(&namespace.profile)?
+            .get(&namespace.audience)
+    }
+
+    pub fn set(&mut self, namespace: &Namespace, tokens: TokenSet) {
+        self.profiles
+            .entry(namespace.profile.clone())
+            .or_default()
+            .insert(namespace.audience.clone(), tokens);
+    }
+
+    pub fn remove(&mut self, namespace: &Namespace) -> Option<TokenSet> {
+        let audiences = self.profiles.get_mut(&namespace.profile)?;
+        let removed = audiences.remove(&namespace.audience);
+        if audiences.is_empty() {
+            self.profiles.remove(&namespace.profile);
+        }
+        removed
+    }
+
+    /// Namespaces with tokens.
+    pub fn namespaces(&self) -> Vec<Namespace> {
+        self.profiles
+            .iter()
+            .flat_map(|(profile, audiences)| {
+                audiences
+                    .keys()
+                    .map(move |audience| Namespace::new(profile, audience))
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty() && self.legacy.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_namespaces() {
+        let default = Namespace::new("default", "https://mamoru.ai");
+        let staging = Namespace::new("staging", "https://mamoru.ai");
+        let mut tokens = Tokens::default();
+        tokens.set(
+            &default,
+            TokenSet {
+                access: "access1".to_string(),
+                ..Default::default()
+            },
+        );
+        tokens.set(
+            &staging,
+            TokenSet {
+                access: "access2".to_string(),
+                expires_at: Some(100),
+                ..Default::default()
+            },
+        );
+
+        let parsed = Tokens::from_json(&tokens.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, tokens);
+        assert_eq!(parsed.get(&default).unwrap().access, "access1");
+        assert!(parsed.get(&staging).unwrap().is_expired(100));
+        assert_eq!(parsed.namespaces(), vec![default.clone(), staging.clone()]);
+
+        tokens.remove(&default);
+        assert_eq!(tokens.get(&default), None);
+        assert_eq!(tokens.namespaces(), vec![staging]);
+    }
+
+    #[test]
+    fn test_legacy_format() {
+        let tokens =
+            Tokens::from_json(r#"{"access_token": "access", "refresh_token": ""}"#).unwrap();
+        assert_eq!(
+            tokens.legacy,
+            Some(TokenSet {
+                access: "access".to_string(),
+                ..Default::default()
+            })
+        );
+        assert!(tokens.namespaces().is_empty());
+
+        assert!(Tokens::from_json("{}").unwrap().is_empty());
+    }
+}
@@ -1,11 +1,73 @@
-pub trait CredStore {
-    fn add(&mut self, key: String, value: String) -> &mut Self;
-    fn get(&self, key: &str) -> Option<&String>;
-    fn clear(&mut self) -> &mut Self;
-    fn keys_present(&self, keys: &[String]) -> bool;
-    fn load(&self) -> Result<Self, std::io::Error>
-    where
-        Self: Sized;
-    fn save(&self) -> Result<(), std::io::Error>;
-    fn delete(&self) -> Result<(), std::io::Error>;
+use std::io::Error;
+
+use async_trait::async_trait;
+
+use crate::{Namespace, TokenSet, Tokens};
+
+/// Storage of the tokens of every profile and audience.
+///
+/// Stores implement [`load`](CredStore::load), [`save`](CredStore::save) and
+/// [`delete`](CredStore::delete), the typed accessors go through [`update`](CredStore::update).
+#[async_trait]
+pub trait CredStore: Send + Sync {
+    /// Reads all tokens, an empty set when nothing was stored yet.
+    async fn load(&self) -> Result<Tokens, Error>;
+    /// Replaces all tokens.
+    async fn save(&self, tokens: &Tokens) -> Result<(), Error>;
+    /// Removes the tokens of every namespace.
+    async fn delete(&self) -> Result<(), Error>;
+
+    /// Loads the tokens, applies `update` and saves them.
+    ///
+    /// File stores hold a lock meanwhile, so parallel invocations don't overwrite each other.
+    async fn update(
+        &self,
+        update: &mut (dyn for<'t> FnMut(&'t mut Tokens) + Send),
+    ) -> Result<(), Error> {
+        let mut tokens = self.load().await?;
+        update(&mut tokens);
+        self.save(&tokens).await
+    }
+
+    async fn get(&self, namespace: &Namespace) -> Result<Option<TokenSet>, Error> {
+        Ok(self.load().await?.get(namespace).cloned())
+    }
+
+    async fn set(&self, namespace: &Namespace, tokens: TokenSet) -> Result<(), Error> {
+        let mut tokens = Some(tokens);
+        self.update(&mut |all| {
+            if let Some(tokens) = tokens.take() {
+                all.set(namespace, tokens);
+            }
+        })
+        .await
+    }
+
+    /// Removes the tokens of `namespace`, returns whether there were any.
+    async fn remove(&self, namespace: &Namespace) -> Result<bool, Error> {
+        let mut removed = false;
+        self.update(&mut |all| removed = all.remove(namespace).is_some())
+            .await?;
+        Ok(removed)
+    }
+
+    /// Moves tokens stored in the previous untyped format to `namespace`, unless it already has
+    /// tokens. Returns whether the store was migrated.
+    async fn migrate(&self, namespace: &Namespace) -> Result<bool, Error> {
+        if self.load().await?.legacy.is_none() {
+            return Ok(false);
+        }
+
+        let mut migrated = false;
+        self.update(&mut |all| {
+            if let Some(legacy) = all.legacy.take() {
+                if all.get(namespace).is_none() {
+                    all.set(namespace, legacy);
+                }
+                migrated = true;
+            }
+        })
+        .await?;
+        Ok(migrated)
+    }
 }
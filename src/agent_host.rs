@@ -0,0 +1,215 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use wasmtime::{
+    component::{Component, Linker, ResourceTable},
+    Config as EngineConfig, Engine, Store,
+};
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiView};
+
+use crate::status;
+
+wasmtime::component::bindgen!({
+    path: "wit/agent.wit",
+    world: "agent",
+});
+
+use mamoru::agent::host::{Host, Incident, IncidentSeverity};
+
+/// Fuel available to an agent for a single run, stops agents stuck in a loop.
+pub const FUEL_LIMIT: u64 = 10_000_000_000;
+
+/// Chain data fed to the agent handler.
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    Block(String),
+    Transaction(String),
+}
+
+/// Incident reported by an agent through the `host.report` import.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportedIncident {
+    pub severity: String,
+    pub message: String,
+    pub address: String,
+    pub data: Vec<u8>,
+}
+
+impl From<Incident> for ReportedIncident {
+    fn from(incident: Incident) -> Self {
+        let severity = match incident.severity {
+            IncidentSeverity::Info => "info",
+            IncidentSeverity::Warning => "warning",
+            IncidentSeverity::Error => "error",
+            IncidentSeverity::Alert => "alert",
+        };
+
+        ReportedIncident {
+            severity: severity.to_string(),
+            message: incident.message,
+            address: incident.address,
+            data: incident.data,
+        }
+    }
+}
+
+struct HostState {
+    parameters: HashMap<String, String>,
+    incidents: Vec<ReportedIncident>,
+    table: ResourceTable,
+    wasi: WasiCtx,
+}
+
+impl Host for HostState {
+    fn parameter(&mut self, key: String) -> Option<String> {
+        self.parameters.get(&key).cloned()
+    }
+
+    fn report(&mut self, incident: Incident) {
+        self.incidents.push(incident.into());
+    }
+
+    fn log(&mut self, message: String) {
+        status!("  [agent] {}", message);
+    }
+}
+
+impl WasiView for HostState {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+}
+
+/// Embedded host implementing the `mamoru:agent` world (see `wit/agent.wit`).
+///
+/// The component is compiled once, every [`AgentHost::run`] gets a fresh instance.
+pub struct AgentHost {
+    engine: Engine,
+    component: Component,
+    linker: Linker<HostState>,
+}
+
+impl AgentHost {
+    pub fn new(wasm: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config = EngineConfig::new();
+        config.wasm_component_model(true).consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let component = Component::from_binary(&engine, wasm)?;
+
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker_sync(&mut linker)?;
+        Agent::add_to_linker(&mut linker, |state: &mut HostState| state)?;
+
+        Ok(AgentHost {
+            engine,
+            component,
+            linker,
+        })
+    }
+
+    /// Feeds `events` to a new agent instance and returns the incidents it reported.
+    pub fn run(
+        &self,
+        parameters: HashMap<String, String>,
+        events: &[AgentEvent],
+    ) -> Result<Vec<ReportedIncident>, Box<dyn std::error::Error>> {
+        let state = HostState {
+            parameters,
+            incidents: vec![],
+            table: ResourceTable::new(),
+            wasi: WasiCtxBuilder::new().inherit_stderr().build(),
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.set_fuel(FUEL_LIMIT)?;
+
+        let agent = Agent::instantiate(&mut store, &self.component, &self.linker)?;
+        let handler = agent.mamoru_agent_handler();
+        for event in events {
+            match event {
+                AgentEvent::Block(block) => handler.call_handle_block(&mut store, block)?,
+                AgentEvent::Transaction(tx) => handler.call_handle_transaction(&mut store, tx)?,
+            }
+        }
+
+        Ok(std::mem::take(&mut store.data_mut().incidents))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Agent reporting an alert for every transaction, it traps on blocks.
+    pub(crate) const TEST_AGENT: &str = r#"
+(component
+  (import "mamoru:agent/host@0.1.0" (instance $host
+    (type $severity (enum "info" "warning" "error" "alert"))
+    (export "incident-severity" (type $severity' (eq $severity)))
+    (type $incident (record
+      (field "severity" $severity')
+      (field "message" string)
+      (field "address" string)
+      (field "data" (list u8))))
+    (export "incident" (type $incident' (eq $incident)))
+    (export "report" (func (param "incident" $incident')))))
+
+  (core module $libc (memory (export "memory") 1))
+  (core instance $libc (instantiate $libc))
+  (alias export $host "report" (func $report))
+  (core func $report (canon lower (func $report) (memory $libc "memory")))
+
+  (core module $agent
+    (import "libc" "memory" (memory 1))
+    (import "host" "report" (func $report (param i32 i32 i32 i32 i32 i32 i32)))
+    (global $heap (mut i32) (i32.const 1024))
+    (data (i32.const 0) "transfer0xabc")
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (global.get $heap)
+      (global.set $heap (i32.add (global.get $heap) (local.get 3))))
+    (func (export "handle-transaction") (param i32 i32)
+      (call $report
+        (i32.const 3) (i32.const 0) (i32.const 8) (i32.const 8) (i32.const 5) (i32.const 0) (i32.const 0)))
+    (func (export "handle-block") (param i32 i32)
+      unreachable))
+  (core instance $agent (instantiate $agent
+    (with "libc" (instance $libc))
+    (with "host" (instance (export "report" (func $report))))))
+
+  (func $handle-transaction (param "tx" string)
+    (canon lift (core func $agent "handle-transaction")
+      (memory $libc "memory") (realloc (func $agent "realloc"))))
+  (func $handle-block (param "block" string)
+    (canon lift (core func $agent "handle-block")
+      (memory $libc "memory") (realloc (func $agent "realloc"))))
+  (instance $handler
+    (export "handle-transaction" (func $handle-transaction))
+    (export "handle-block" (func $handle-block)))
+  (export "mamoru:agent/handler@0.1.0" (instance $handler)))
+"#;
+
+    #[test]
+    fn test_agent_host() {
+        let host = AgentHost::new(&wat::parse_str(TEST_AGENT).unwrap()).unwrap();
+        let transaction = AgentEvent::Transaction(r#"{"hash":"0x01"}"#.to_string());
+
+        let incidents = host
+            .run(HashMap::new(), &[transaction.clone(), transaction])
+            .unwrap();
+        assert_eq!(incidents.len(), 2);
+        assert_eq!(
+            incidents[0],
+            ReportedIncident {
+                severity: "alert".to_string(),
+                message: "transfer".to_string(),
+                address: "0xabc".to_string(),
+                data: vec![],
+            }
+        );
+
+        let block = AgentEvent::Block(r#"{"height":1}"#.to_string());
+        assert!(host.run(HashMap::new(), &[block]).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Targets agents can be built for, newest first.
+pub const WASM_TARGETS: [&str; 3] = ["wasm32-wasip2", "wasm32-wasip1", "wasm32-wasi"];
+
+#[derive(Debug, Deserialize)]
+struct CargoMetadata {
+    packages: Vec<MetadataPackage>,
+    target_directory: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataPackage {
+    name: String,
+    manifest_path: PathBuf,
+    targets: Vec<MetadataTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataTarget {
+    name: String,
+    kind: Vec<String>,
+}
+
+/// Finds the release wasm artifact of the agent crate in `dir_path` using `cargo metadata`.
+///
+/// Honors `CARGO_TARGET_DIR` and workspaces. When artifacts exist for several targets the most
+/// recently built one is returned.
+pub fn resolve_wasm_file(dir_path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .current_dir(dir_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    let metadata: CargoMetadata = serde_json::from_slice(&output.stdout)?;
+    let package = select_package(&metadata, dir_path)
+        .ok_or_else(|| format!("no cargo package found in {}", dir_path.display()))?;
+
+    let candidates = artifact_candidates(&metadata.target_directory, &artifact_name(package));
+    candidates
+        .into_iter()
+        .filter(|path| path.exists())
+        .max_by_key(|path| path.metadata().and_then(|m| m.modified()).ok())
+        .ok_or_else(|| {
+            format!(
+                "no wasm artifact found for {} in {}, build the agent with `cargo-component build --release`",
+                package.name,
+                metadata.target_directory.display()
+            )
+            .into()
+        })
+}
+
+/// Picks the package whose manifest is in `dir_path`, or the only package of the workspace.
+fn select_package<'a>(metadata: &'a CargoMetadata, dir_path: &Path) -> Option<&'a MetadataPackage> {
+    let dir_path = dir_path
+        .canonicalize()
+        .unwrap_or_else(|_| dir_path.to_path_buf());
+
+    metadata
+        .packages
+        .iter()
+        .find(|package| package.manifest_path.parent() == Some(dir_path.as_path()))
+        .or(match metadata.packages.as_slice() {
+            [package] => Some(package),
+            _ => None,
+        })
+}
+
+/// Returns the file name of the package library, cargo replaces `-` with `_` in it.
+fn artifact_name(package: &MetadataPackage) -> String {
+    let name = package
+        .targets
+        .iter()
+        .find(|target| target.kind.iter().any(|kind| kind == "cdylib"))
+        .map_or(package.name.as_str(), |target| target.name.as_str());
+
+    format!("{}.wasm", name.replace('-', "_"))
+}
+
+fn artifact_candidates(target_directory: &Path, artifact_name: &str) -> Vec<PathBuf> {
+    WASM_TARGETS
+        .iter()
+        .map(|target| {
+            target_directory
+                .join(target)
+                .join("release")
+                .join(artifact_name)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"{
+        "packages": [
+            {
+                "name": "sui-agent",
+                "manifest_path": "/workspace/agents/sui/Cargo.toml",
+                "targets": [{"name": "sui-agent", "kind": ["cdylib"]}]
+            },
+            {
+                "name": "common",
+                "manifest_path": "/workspace/common/Cargo.toml",
+                "targets": [{"name": "common", "kind": ["lib"]}]
+            }
+        ],
+        "target_directory": "/workspace/target",
+        "workspace_root": "/workspace"
+    }"#;
+
+    #[test]
+    fn test_select_package() {
+        let metadata: CargoMetadata = serde_json::from_str(METADATA).unwrap();
+
+        let package = select_package(&metadata, Path::new("/workspace/agents/sui")).unwrap();
+        assert_eq!(package.name, "sui-agent");
+        assert_eq!(artifact_name(package), "sui_agent.wasm");
+
+        assert!(select_package(&metadata, Path::new("/workspace")).is_none());
+    }
+
+    #[test]
+    fn test_artifact_candidates() {
+        let candidates = artifact_candidates(Path::new("/workspace/target"), "agent.wasm");
+
+        assert_eq!(
+            candidates,
+            vec![
+                PathBuf::from("/workspace/target/wasm32-wasip2/release/agent.wasm"),
+                PathBuf::from("/workspace/target/wasm32-wasip1/release/agent.wasm"),
+                PathBuf::from("/workspace/target/wasm32-wasi/release/agent.wasm"),
+            ]
+        );
+    }
+}
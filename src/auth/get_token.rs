@@ -1,10 +1,10 @@
-use crate::CommandContext;
+use crate::{config::Config, status, CommandContext};
 use base64::Engine;
 use cred_store::CredStore;
 use serde::Deserialize;
 use tracing::info;
 
-use super::TokenResponse;
+use super::{TokenResponse, CLIENT_CREDENTIALS_GRANT};
 
 #[derive(Debug, Deserialize)]
 struct Claims {
@@ -51,72 +51,113 @@ pub async fn refresh_access_token(
             ("refresh_token", refresh_token),
         ])
         .send()
-        .await
-        .expect("send");
-
-    match response.json::<TokenResponse>().await {
-        Ok(response) => Ok(response),
-        Err(e) => {
-            println!("Error refreshing access token: {:?}", e);
-            Err(Box::new(e))
-        }
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!(
+            "token refresh failed ({}): {}",
+            status,
+            response.text().await?
+        )
+        .into());
+    }
+    Ok(response.json::<TokenResponse>().await?)
+}
+
+/// Requests a token for the `MAMORU_CLIENT_ID` application with its `MAMORU_CLIENT_SECRET`.
+pub async fn request_client_credentials_token(
+    config: &Config,
+) -> Result<TokenResponse, Box<dyn std::error::Error>> {
+    if config.mamoru_client_id.is_empty() || config.mamoru_client_secret.is_empty() {
+        return Err("MAMORU_CLIENT_ID and MAMORU_CLIENT_SECRET required".into());
+    }
+    let token_endpoint = format!("{}/oauth/token", config.mamoru_cli_auth0_domain);
+
+    let response = reqwest::Client::new()
+        .post(token_endpoint)
+        .form(&[
+            ("grant_type", CLIENT_CREDENTIALS_GRANT),
+            ("client_id", config.mamoru_client_id.as_str()),
+            ("client_secret", config.mamoru_client_secret.as_str()),
+            ("audience", config.mamoru_cli_auth0_audience.as_str()),
+        ])
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!(
+            "token request failed ({}): {}",
+            status,
+            response.text().await?
+        )
+        .into());
     }
+    Ok(response.json::<TokenResponse>().await?)
 }
 
+/// Access token of the current namespace, refreshed and saved when it expired.
 pub async fn get_token<T: CredStore>(
-    context: &mut CommandContext<'_, T>,
-) -> Result<Option<String>, Box<dyn std::error::Error>>
-where
-    T: CredStore,
-{
-    let mut credentials = context.cred_store.load()?;
-    let access_token = credentials.get("access_token").cloned();
-    let refresh_token = credentials.get("refresh_token").cloned();
-
-    let token = match (access_token, refresh_token) {
-        (Some(at), Some(rt)) => {
-            if is_token_expired(&at) {
-                println!("Access token expired. Refreshing...");
-                info!("Access token expired. Refreshing...");
-                let token_response = refresh_access_token(
-                    &context.config.mamoru_cli_auth0_domain,
-                    &context.config.mamoru_cli_auth0_client_id,
-                    &rt,
-                )
-                .await?;
-                let new_access_token = match token_response.access_token {
-                    Some(at) => at,
-                    None => {
-                        println!("Couldn't refresh access token.");
-                        return Err("Couldn't refresh access token.".into());
-                    }
-                };
-                let new_refresh_token = match token_response.refresh_token {
-                    Some(rt) => rt,
-                    None => "".to_string(),
-                };
-
-                info!("Access token refreshed.");
-                credentials
-                    .add("access_token".to_string(), new_access_token.clone())
-                    .add("refresh_token".to_string(), new_refresh_token);
-
-                credentials.save()?;
-
-                Ok(Some(new_access_token))
-            } else {
-                Ok(Some(at))
-            }
+    context: &CommandContext<'_, T>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let namespace = context.namespace();
+    let tokens = match context.cred_store().await?.get(&namespace).await? {
+        Some(tokens) => tokens,
+        None => return Ok(None),
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    if !tokens.is_expired(now) && !is_token_expired(&tokens.access) {
+        return Ok(Some(tokens.access));
+    }
+    let token_response = if tokens.grant.as_deref() == Some(CLIENT_CREDENTIALS_GRANT) {
+        status!("Access token expired. Requesting a new one...");
+        info!("Access token expired. Requesting a new one...");
+        request_client_credentials_token(context.config).await?
+    } else {
+        let refresh_token = match &tokens.refresh {
+            Some(rt) => rt,
+            None => return Err("Access token expired".into()),
+        };
+
+        status!("Access token expired. Refreshing...");
+        info!("Access token expired. Refreshing...");
+        refresh_access_token(
+            &context.config.mamoru_cli_auth0_domain,
+            &context.config.mamoru_cli_auth0_client_id,
+            refresh_token,
+        )
+        .await?
+    };
+    let mut refreshed = match token_response.token_set(now) {
+        Some(refreshed) => refreshed,
+        None => {
+            eprintln!("Couldn't refresh access token.");
+            return Err("Couldn't refresh access token.".into());
         }
-        _ => Ok(None),
     };
+    // the refresh token is only returned when it's rotated
+    if refreshed.refresh.is_none() {
+        refreshed.refresh = tokens.refresh;
+    }
+    refreshed.grant = tokens.grant;
 
-    token
+    info!("Access token refreshed.");
+    let access_token = refreshed.access.clone();
+    context
+        .cred_store()
+        .await?
+        .set(&namespace, refreshed)
+        .await?;
+
+    Ok(Some(access_token))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mockito::mock;
 
     #[test]
     fn test_decode_claims_without_verification() {
@@ -124,4 +165,19 @@ mod tests {
         let claims = decode_claims_without_verification(test_token).unwrap();
         assert_eq!(claims.exp, 1697118678);
     }
+
+    #[tokio::test]
+    async fn test_refresh_access_token_error() {
+        let _m = mock("POST", "/oauth/token")
+            .with_status(403)
+            .with_body(r#"{"error":"invalid_grant"}"#)
+            .create();
+
+        let err = refresh_access_token("http://localhost:1234", "client", "refresh")
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("403"));
+        assert!(err.to_string().contains("invalid_grant"));
+    }
 }
@@ -1,7 +1,13 @@
-use serde::{Deserialize, Serialize};
+use cred_store::TokenSet;
+use serde::{Deserialize, Deserializer, Serialize};
 
 pub mod get_token;
 pub mod jwtverifier;
+pub mod pkce;
+
+/// Grant of `login --client-credentials`, stored with the tokens so `get_token` requests a new
+/// token instead of refreshing it.
+pub const CLIENT_CREDENTIALS_GRANT: &str = "client_credentials";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenResponse {
@@ -13,13 +19,60 @@ pub struct TokenResponse {
     pub token_type: Option<String>,
 }
 
+impl TokenResponse {
+    /// Tokens to store, `None` without an access token. `expires_in` is counted from `now`.
+    pub fn token_set(&self, now: i64) -> Option<TokenSet> {
+        Some(TokenSet {
+            access: self.access_token.clone()?,
+            refresh: self.refresh_token.clone(),
+            id_token: self.id_token.clone(),
+            expires_at: self.expires_in.map(|expires_in| now + expires_in as i64),
+            scope: self.scope.clone(),
+            grant: None,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Claims {
     pub iss: String,
     pub sub: String,
+    /// A single audience is a string in client credentials tokens.
+    #[serde(deserialize_with = "one_or_many")]
     pub aud: Vec<String>,
     pub iat: usize,
     pub exp: usize,
     pub azp: String,
+    #[serde(default)]
     pub scope: String,
 }
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(aud) => vec![aud],
+        OneOrMany::Many(aud) => aud,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_credentials_claims() {
+        let claims: Claims = serde_json::from_str(
+            r#"{"iss": "https://auth/", "sub": "ci@clients", "aud": "https://mamoru.ai",
+                "iat": 1, "exp": 2, "azp": "ci", "gty": "client-credentials"}"#,
+        )
+        .unwrap();
+        assert_eq!(claims.aud, vec!["https://mamoru.ai".to_string()]);
+        assert_eq!(claims.scope, "");
+    }
+}
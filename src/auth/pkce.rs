@@ -0,0 +1,179 @@
+use std::{collections::HashMap, io::Error};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+
+/// Path of the redirect URI served by [`LoopbackListener`].
+const CALLBACK_PATH: &str = "/callback";
+/// Port of the redirect URI, `http://127.0.0.1:8765/callback` must be an allowed callback URL of
+/// the Auth0 application.
+pub const DEFAULT_CALLBACK_PORT: u16 = 8765;
+const CALLBACK_PAGE: &str = "<html><body><p>Login finished, you can close this window and return to the terminal.</p></body></html>";
+
+/// Code verifier and challenge of an authorization code request (RFC 7636), with the `state`
+/// the redirect must return.
+#[derive(Debug, Clone)]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+    pub state: String,
+}
+
+impl Pkce {
+    /// Random verifier and state.
+    pub fn generate() -> Self {
+        Pkce::from_verifier(random_string(32), random_string(16))
+    }
+
+    fn from_verifier(verifier: String, state: String) -> Self {
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        Pkce {
+            verifier,
+            challenge,
+            state,
+        }
+    }
+}
+
+/// URL safe encoding of `len` random bytes.
+fn random_string(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// HTTP listener on 127.0.0.1 receiving the redirect of the browser.
+pub struct LoopbackListener {
+    listener: TcpListener,
+}
+
+impl LoopbackListener {
+    /// Listens on `port`, any free port when 0.
+    pub async fn bind(port: u16) -> Result<Self, Error> {
+        Ok(LoopbackListener {
+            listener: TcpListener::bind(("127.0.0.1", port)).await?,
+        })
+    }
+
+    pub fn redirect_uri(&self) -> Result<String, Error> {
+        Ok(format!(
+            "http://127.0.0.1:{}{}",
+            self.listener.local_addr()?.port(),
+            CALLBACK_PATH
+        ))
+    }
+
+    /// Waits for the redirect and returns its query parameters. Other requests, like the
+    /// favicon, are answered with 404.
+    pub async fn receive(&self) -> Result<HashMap<String, String>, Error> {
+        loop {
+            let (mut stream, _) = self.listener.accept().await?;
+            let (read, mut write) = stream.split();
+            let mut request_line = String::new();
+            BufReader::new(read).read_line(&mut request_line).await?;
+
+            match parse_redirect(&request_line) {
+                Some(params) => {
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        CALLBACK_PAGE.len(),
+                        CALLBACK_PAGE
+                    );
+                    write.write_all(response.as_bytes()).await?;
+                    return Ok(params);
+                }
+                None => {
+                    write
+                        .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                        .await?;
+                }
+            }
+        }
+    }
+}
+
+/// Query parameters of a `GET /callback` request line, `None` for other requests.
+fn parse_redirect(request_line: &str) -> Option<HashMap<String, String>> {
+    let mut parts = request_line.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+
+    let url = url::Url::parse(&format!("http://127.0.0.1{}", parts.next()?)).ok()?;
+    if url.path() != CALLBACK_PATH {
+        return None;
+    }
+    Some(url.query_pairs().into_owned().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{io::AsyncReadExt, net::TcpStream};
+
+    #[test]
+    fn test_pkce_challenge() {
+        // example of RFC 7636, appendix B
+        let pkce = Pkce::from_verifier(
+            "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string(),
+            String::new(),
+        );
+        assert_eq!(
+            pkce.challenge,
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+
+        let pkce = Pkce::generate();
+        assert_eq!(pkce.verifier.len(), 43);
+        assert_ne!(pkce.state, Pkce::generate().state);
+    }
+
+    #[test]
+    fn test_parse_redirect() {
+        let params = parse_redirect("GET /callback?code=abc&state=xyz%3D HTTP/1.1\r\n").unwrap();
+        assert_eq!(params["code"], "abc");
+        assert_eq!(params["state"], "xyz=");
+
+        assert!(parse_redirect("GET /favicon.ico HTTP/1.1\r\n").is_none());
+        assert!(parse_redirect("").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_loopback_listener() {
+        let listener = LoopbackListener::bind(0).await.unwrap();
+        let redirect_uri = listener.redirect_uri().unwrap();
+        let addr = redirect_uri
+            .trim_start_matches("http://")
+            .trim_end_matches(CALLBACK_PATH)
+            .to_string();
+
+        let browser = tokio::spawn(async move {
+            let mut favicon = TcpStream::connect(&addr).await.unwrap();
+            favicon
+                .write_all(b"GET /favicon.ico HTTP/1.1\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            favicon.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 404"));
+
+            let mut redirect = TcpStream::connect(&addr).await.unwrap();
+            redirect
+                .write_all(b"GET /callback?code=abc&state=xyz HTTP/1.1\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            redirect.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 200"));
+        });
+
+        let params = listener.receive().await.unwrap();
+        assert_eq!(params["code"], "abc");
+        browser.await.unwrap();
+    }
+}
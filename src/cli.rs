@@ -0,0 +1,489 @@
+use clap::{Args, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, Shell};
+use std::path::PathBuf;
+
+use crate::{
+    auth::pkce::DEFAULT_CALLBACK_PORT, completion, config::Config, errors::CliError,
+    output::OutputFormat,
+};
+
+/// Gas limit used when neither `--gas-limit` nor `MAMORU_GAS_LIMIT` is set.
+pub const DEFAULT_GAS_LIMIT: u64 = 200_000_000;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "mamoru cli tool", arg_required_else_help = true)]
+pub struct Cli {
+    /// Output format of the command result
+    #[arg(long, value_name = "FORMAT", default_value = "table", global = true)]
+    pub output: OutputFormat,
+
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Manage agents
+    #[command(subcommand, arg_required_else_help = true)]
+    Agent(AgentCommands),
+    /// Deploy agents described in a workspace file
+    Deploy(DeployArgs),
+    /// Manifest commands
+    #[command(subcommand, arg_required_else_help = true)]
+    Manifest(ManifestCommands),
+    /// Authentication commands
+    #[command(subcommand, arg_required_else_help = true)]
+    Auth(AuthCommands),
+    /// Show the logged in user, same as `auth status`
+    Whoami,
+    /// Logout from mamoru, revoking the refresh token
+    Logout(LogoutArgs),
+    /// Login to mamoru
+    Login(LoginArgs),
+    /// Print the shell completion script
+    ///
+    /// `--chain-name` completes the chains of the agent in the current directory and the chains
+    /// the validation chain listed during earlier `agent publish` or `manifest init` runs, no
+    /// request is made while completing.
+    Completions {
+        /// Shell to complete in
+        shell: Shell,
+    },
+    /// Print the man page, or write the pages of every command to a directory
+    Man {
+        /// Directory for the pages of every command
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuthCommands {
+    /// Show the logged in user, the token expiry and the organizations
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AgentCommands {
+    /// Publish an agent
+    #[command(arg_required_else_help = true)]
+    Publish(PublishArgs),
+    /// Build an agent with cargo-component
+    Build {
+        /// Path to Agent directory
+        #[arg(default_value = ".")]
+        file: PathBuf,
+        /// Target to build for: wasm32-wasip2, wasm32-wasip1 or wasm32-wasi
+        #[arg(long)]
+        target: Option<String>,
+    },
+    /// Run an agent locally against recorded chain data
+    Test(TestArgs),
+    /// Create a new agent
+    New(NewArgs),
+    /// Publish an agent to existing metadata
+    #[command(arg_required_else_help = true)]
+    Launch(LaunchArgs),
+    /// Assign an agent to an organization
+    Assign(AssignArgs),
+    /// Unregister an agent
+    Unregister(UnregisterArgs),
+    /// Replace a deployed agent with a new version
+    #[command(arg_required_else_help = true)]
+    Upgrade(UpgradeArgs),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ManifestCommands {
+    /// Create manifest.yaml interactively
+    Init {
+        /// gRPC URL used to list the supported chains
+        #[arg(short, long, env = "MAMORU_RPC_URL")]
+        grpc: Option<String>,
+        /// Overwrite an existing manifest.yaml without asking
+        #[arg(long)]
+        force: bool,
+        /// Path to Agent directory
+        #[arg(default_value = ".")]
+        file: PathBuf,
+    },
+}
+
+/// Connection to the validation chain, values that aren't set are read from the settings file.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ChainArgs {
+    /// gRPC URL
+    #[arg(long, env = "MAMORU_RPC_URL")]
+    pub grpc: Option<String>,
+    /// Private key
+    #[arg(short, long, env = "MAMORU_PRIVATE_KEY", hide_env_values = true)]
+    pub key: Option<String>,
+    /// Gas limit [default: 200000000]
+    #[arg(long, env = "MAMORU_GAS_LIMIT")]
+    pub gas_limit: Option<u64>,
+    /// Chain ID
+    #[arg(long, env = "MAMORU_CHAIN_ID")]
+    pub chain_id: Option<String>,
+}
+
+/// Chain settings resolved by [`ChainArgs::resolve`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainConnection {
+    pub grpc: String,
+    pub prkey: String,
+    pub gas_limit: u64,
+    pub chain_id: String,
+}
+
+impl ChainArgs {
+    /// Only resolves the gRPC URL, for commands that query the chain before signing anything.
+    pub fn grpc(&self, config: &Config) -> Result<String, CliError> {
+        or_config(self.grpc.as_ref(), &config.mamoru_rpc_url, "gRPC URL")
+    }
+
+    pub fn resolve(&self, config: &Config) -> Result<ChainConnection, CliError> {
+        let gas_limit = match self.gas_limit {
+            Some(gas_limit) => gas_limit,
+            None if config.mamoru_gas_limit.is_empty() => DEFAULT_GAS_LIMIT,
+            None => config
+                .mamoru_gas_limit
+                .parse()
+                .map_err(|_| CliError::Config("gas limit must be a number".to_string()))?,
+        };
+
+        Ok(ChainConnection {
+            grpc: self.grpc(config)?,
+            prkey: or_config(self.key.as_ref(), &config.mamoru_private_key, "Private key")?,
+            gas_limit,
+            chain_id: or_config(self.chain_id.as_ref(), &config.mamoru_chain_id, "Chain ID")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct OrganizationArgs {
+    /// Organization ID
+    #[arg(short, long, env = "MAMORU_ORGANIZATION_ID")]
+    pub organization_id: Option<String>,
+}
+
+impl OrganizationArgs {
+    pub fn resolve(&self, config: &Config) -> Result<String, CliError> {
+        or_config(
+            self.organization_id.as_ref(),
+            &config.mamoru_organization_id,
+            "Organization ID",
+        )
+    }
+
+    /// Organization for commands where it's optional.
+    pub fn optional(&self, config: &Config) -> Option<String> {
+        self.resolve(config).ok()
+    }
+}
+
+/// Wasm artifact to use, shared by the commands that run or upload the agent.
+#[derive(Debug, Clone, Default, Args)]
+pub struct WasmArgs {
+    /// Build the agent with cargo-component first
+    #[arg(long)]
+    pub build: bool,
+    /// Path to the agent wasm file
+    #[arg(long, value_name = "FILE", conflicts_with = "build")]
+    pub wasm: Option<PathBuf>,
+    /// Target to build for with `--build`: wasm32-wasip2, wasm32-wasip1 or wasm32-wasi
+    #[arg(long, requires = "build")]
+    pub target: Option<String>,
+}
+
+/// Deployment recorded in the agent directory, used when `--agent-id` is omitted.
+#[derive(Debug, Clone, Args)]
+pub struct DeploymentArgs {
+    /// Agent ID, defaults to the last deployment
+    #[arg(short = 'd', long, add = ArgValueCandidates::new(completion::agent_ids))]
+    pub agent_id: Option<String>,
+    /// Chain name of the deployment
+    #[arg(short, long, add = ArgValueCandidates::new(completion::chain_names))]
+    pub chain_name: Option<String>,
+    /// Path to Agent directory with deployment state
+    #[arg(long, default_value = ".")]
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct PublishArgs {
+    #[command(flatten)]
+    pub chain: ChainArgs,
+    /// Chain name, can be repeated
+    #[arg(
+        short,
+        long = "chain-name",
+        required_unless_present = "all_supported",
+        add = ArgValueCandidates::new(completion::chain_names)
+    )]
+    pub chain_names: Vec<String>,
+    /// Publish to every chain supported by the manifest
+    #[arg(long, conflicts_with = "chain_names")]
+    pub all_supported: bool,
+    /// Always register new metadata, even if identical metadata exists
+    #[arg(long)]
+    pub new_metadata: bool,
+    #[command(flatten)]
+    pub wasm: WasmArgs,
+    /// Strip custom sections and optimize the wasm for size before publishing
+    #[arg(long)]
+    pub optimize: bool,
+    #[command(flatten)]
+    pub organization: OrganizationArgs,
+    /// Path to Agent directory
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct TestArgs {
+    /// Directory with JSON fixtures, defaults to <FILE>/fixtures
+    #[arg(long, value_name = "DIR")]
+    pub fixtures: Option<PathBuf>,
+    /// Parameter for every fixture, as KEY=VALUE
+    #[arg(short, long = "param", value_name = "PARAM", value_parser = parse_key_value)]
+    pub params: Vec<(String, String)>,
+    #[command(flatten)]
+    pub wasm: WasmArgs,
+    /// Path to Agent directory
+    #[arg(default_value = ".")]
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct NewArgs {
+    /// Agent name
+    #[arg(short = 'n', value_name = "name", default_value = "new-agent")]
+    pub name: String,
+    /// Built-in template name, git URL or template directory
+    #[arg(short, long)]
+    pub template: Option<String>,
+    /// Chain the agent monitors, can be repeated
+    #[arg(short, long = "chain-name", add = ArgValueCandidates::new(completion::chain_names))]
+    pub chain_names: Vec<String>,
+    /// Agent description
+    #[arg(short, long)]
+    pub description: Option<String>,
+    /// List the built-in templates
+    #[arg(long)]
+    pub list_templates: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct LaunchArgs {
+    /// Metadata ID, defaults to the last deployment
+    #[arg(short, long)]
+    pub metadata_id: Option<String>,
+    /// Chain name
+    #[arg(short, long, add = ArgValueCandidates::new(completion::chain_names))]
+    pub chain_name: String,
+    #[command(flatten)]
+    pub chain: ChainArgs,
+    #[command(flatten)]
+    pub organization: OrganizationArgs,
+    /// Path to Agent directory
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct LoginArgs {
+    /// Login as the application of MAMORU_CLIENT_ID and MAMORU_CLIENT_SECRET, without a browser
+    #[arg(long)]
+    pub client_credentials: bool,
+    /// Login in the browser, redirected back to the CLI instead of entering a device code
+    #[arg(long, conflicts_with = "client_credentials")]
+    pub browser: bool,
+    /// Local port the browser is redirected to, http://127.0.0.1:<PORT>/callback must be an
+    /// allowed callback URL of the Auth0 application
+    #[arg(long, default_value_t = DEFAULT_CALLBACK_PORT, requires = "browser")]
+    pub port: u16,
+}
+
+#[derive(Debug, Args)]
+pub struct LogoutArgs {
+    /// Logout from every profile, not only the current one
+    #[arg(long)]
+    pub all: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct AssignArgs {
+    #[command(flatten)]
+    pub deployment: DeploymentArgs,
+    #[command(flatten)]
+    pub organization: OrganizationArgs,
+    /// GraphQL URL
+    #[arg(long, env = "MAMORU_GRAPHQL_URL")]
+    pub graphql_url: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct UnregisterArgs {
+    #[command(flatten)]
+    pub deployment: DeploymentArgs,
+    #[command(flatten)]
+    pub chain: ChainArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct UpgradeArgs {
+    #[command(flatten)]
+    pub chain: ChainArgs,
+    /// Chain name of the deployment
+    #[arg(short, long, add = ArgValueCandidates::new(completion::chain_names))]
+    pub chain_name: Option<String>,
+    /// Agent ID to replace, defaults to the last deployment
+    #[arg(short = 'd', long, requires = "chain_name", add = ArgValueCandidates::new(completion::agent_ids))]
+    pub agent_id: Option<String>,
+    /// Always register new metadata, even if identical metadata exists
+    #[arg(long)]
+    pub new_metadata: bool,
+    #[command(flatten)]
+    pub wasm: WasmArgs,
+    /// Strip custom sections and optimize the wasm for size before publishing
+    #[arg(long)]
+    pub optimize: bool,
+    #[command(flatten)]
+    pub organization: OrganizationArgs,
+    /// Path to Agent directory
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct DeployArgs {
+    /// Path to the deploy file
+    #[arg(short, long, default_value = "mamoru.deploy.yaml")]
+    pub file: PathBuf,
+    /// Only show the plan
+    #[arg(long)]
+    pub plan: bool,
+    /// Apply the plan without confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+    #[command(flatten)]
+    pub chain: ChainArgs,
+    /// Always register new metadata, even if identical metadata exists
+    #[arg(long)]
+    pub new_metadata: bool,
+    /// Strip custom sections and optimize the wasm for size before publishing
+    #[arg(long)]
+    pub optimize: bool,
+    // organization of the agents without one in the deploy file
+    #[command(flatten)]
+    pub organization: OrganizationArgs,
+}
+
+/// Whether `grpc` is a local validation chain, which doesn't need credentials.
+pub fn is_local_grpc(grpc: &str) -> bool {
+    grpc == "http://localhost:9090" || grpc == "http://127.0.0.1:9090"
+}
+
+fn or_config(value: Option<&String>, config_value: &str, name: &str) -> Result<String, CliError> {
+    match value {
+        Some(value) => Ok(value.clone()),
+        None if config_value.is_empty() => Err(CliError::Config(format!("{} required", name))),
+        None => Ok(config_value.to_string()),
+    }
+}
+
+fn parse_key_value(param: &str) -> Result<(String, String), String> {
+    param
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("invalid parameter {}, expected KEY=VALUE", param))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn config() -> Config {
+        Config {
+            mamoru_cli_auth0_domain: String::new(),
+            mamoru_cli_auth0_client_id: String::new(),
+            mamoru_cli_auth0_audience: String::new(),
+            mamoru_rpc_url: "http://rpc_url".to_string(),
+            mamoru_private_key: "private_key".to_string(),
+            mamoru_gas_limit: String::new(),
+            mamoru_graphql_url: String::new(),
+            mamoru_chain_id: "chain_id".to_string(),
+            mamoru_organization_id: String::new(),
+            mamoru_profile: "default".to_string(),
+            mamoru_cred_store: "file".to_string(),
+            mamoru_client_id: String::new(),
+            mamoru_client_secret: String::new(),
+            mamoru_cli_auth0_issuer: None,
+            mamoru_cli_auth0_scopes: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_chain_args_resolve() {
+        let args = ChainArgs {
+            key: Some("key".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            args.resolve(&config()).unwrap(),
+            ChainConnection {
+                grpc: "http://rpc_url".to_string(),
+                prkey: "key".to_string(),
+                gas_limit: DEFAULT_GAS_LIMIT,
+                chain_id: "chain_id".to_string(),
+            }
+        );
+
+        let mut config = config();
+        config.mamoru_chain_id = String::new();
+        assert_eq!(
+            args.resolve(&config),
+            Err(CliError::Config("Chain ID required".to_string()))
+        );
+        assert_eq!(
+            OrganizationArgs::default().resolve(&config),
+            Err(CliError::Config("Organization ID required".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let cli = Cli::try_parse_from([
+            "mamorurs-cli",
+            "agent",
+            "publish",
+            "-c",
+            "SUI_MAINNET",
+            "-c",
+            "APTOS_MAINNET",
+            "--gas-limit",
+            "1000",
+            "--output",
+            "json",
+            "./agent",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.output, OutputFormat::Json);
+        match cli.command {
+            Commands::Agent(AgentCommands::Publish(args)) => {
+                assert_eq!(args.chain_names, vec!["SUI_MAINNET", "APTOS_MAINNET"]);
+                assert_eq!(args.chain.gas_limit, Some(1000));
+                assert_eq!(args.file, PathBuf::from("./agent"));
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+
+        assert!(Cli::try_parse_from(["mamorurs-cli", "agent", "test", "-p", "threshold"]).is_err());
+    }
+}
@@ -6,16 +6,30 @@ use mamoru_chain_client::{
     QueryClientConfig, SendMode,
 };
 
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashSet;
 use url::Url;
 
-use crate::errors::ResponseData;
+use crate::{
+    errors::{self, CliError, ResponseData},
+    status,
+};
+
+/// Bech32 prefix of the validation chain accounts.
+const ACCOUNT_PREFIX: &str = "mamoru";
 
-#[allow(dead_code)]
-pub async fn query_client(grpc_url: Url) -> QueryClient {
-    QueryClient::connect(query_client_config(grpc_url))
+/// Parses the gRPC URL of the validation chain.
+pub fn grpc_url(grpc: &str) -> Result<Url, CliError> {
+    grpc.parse::<Url>()
+        .map_err(|e| CliError::Config(format!("invalid gRPC URL {}: {}", grpc, e)))
+}
+
+pub async fn query_client(grpc_url: Url) -> Result<QueryClient, CliError> {
+    QueryClient::connect(query_client_config(grpc_url.clone()))
         .await
-        .expect("QueryClient::connect error.")
+        .map_err(|e| CliError::Chain(format!("couldn't connect to {}: {:?}", grpc_url, e)))
 }
 
 pub async fn message_client(
@@ -23,13 +37,12 @@ pub async fn message_client(
     grpc_url: &Url,
     gas_limit: u64,
     chain_id: String,
-) -> MessageClient {
-    MessageClient::connect(message_client_config(prkey, grpc_url, gas_limit, chain_id).await)
+) -> Result<MessageClient, CliError> {
+    MessageClient::connect(message_client_config(prkey, grpc_url, gas_limit, chain_id).await?)
         .await
-        .expect("MessageClient::connect error.")
+        .map_err(|e| CliError::Chain(format!("couldn't connect to {}: {:?}", grpc_url, e)))
 }
 
-#[allow(dead_code)]
 pub fn query_client_config(grpc_url: Url) -> QueryClientConfig {
     QueryClientConfig {
         connection: ConnectionConfig { endpoint: grpc_url },
@@ -42,11 +55,11 @@ pub async fn message_client_config(
     grpc_url: &Url,
     gas_limit: u64,
     chain_id: String,
-) -> MessageClientConfig {
-    let private_key: cosmrs::crypto::secp256k1::SigningKey = string_to_signing_key(key.as_str());
+) -> Result<MessageClientConfig, CliError> {
+    let private_key: cosmrs::crypto::secp256k1::SigningKey = string_to_signing_key(key.as_str())?;
     let builder = Config::builder()
         .set_default("max_decoding_message_size", (20 * 1024 * 1024).to_string())
-        .unwrap()
+        .map_err(|e| CliError::Config(e.to_string()))?
         .add_source(
             config::Environment::with_prefix("MAMORU")
                 .try_parsing(true)
@@ -57,7 +70,7 @@ pub async fn message_client_config(
             let connect_config = ConnectionConfig {
                 endpoint: grpc_url.to_owned(),
             };
-            MessageClientConfig {
+            Ok(MessageClientConfig {
                 connection: connect_config,
                 chain: ChainConfig {
                     tx_gas_limit: gas_limit,
@@ -66,17 +79,43 @@ pub async fn message_client_config(
                 },
                 account: AccountConfig::new(private_key),
                 send_mode: SendMode::Block,
-            }
+            })
         }
-        Err(e) => panic!("Error: {}", e),
+        Err(e) => Err(CliError::Config(format!("invalid client settings: {}", e))),
     }
 }
 
-fn string_to_signing_key(private_key_str: &str) -> secp256k1::SigningKey {
+fn string_to_signing_key(private_key_str: &str) -> Result<secp256k1::SigningKey, CliError> {
     let secret_key_bytes = BASE64_STANDARD
         .decode(private_key_str)
-        .expect("Can not parse private key base64");
-    secp256k1::SigningKey::from_slice(&secret_key_bytes).expect("Can not parse private key bytes")
+        .map_err(|e| CliError::Config(format!("private key is not valid base64: {}", e)))?;
+    secp256k1::SigningKey::from_slice(&secret_key_bytes)
+        .map_err(|e| CliError::Config(format!("invalid private key: {}", e)))
+}
+
+/// Returns the validation chain address of the account owning `private_key_str`.
+pub fn signer_address(private_key_str: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let signing_key = string_to_signing_key(private_key_str)?;
+    let account_id = signing_key.public_key().account_id(ACCOUNT_PREFIX)?;
+
+    Ok(account_id.to_string())
+}
+
+/// Returns the IDs of all daemons registered on the validation chain.
+pub async fn list_daemon_ids(
+    query_client: &QueryClient,
+) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    let daemons = query_client.list_daemons().try_collect::<Vec<_>>().await?;
+
+    Ok(daemons.into_iter().map(|daemon| daemon.daemon_id).collect())
+}
+
+/// Returns the names of the chains supported by the validation chain.
+pub async fn list_chain_names(grpc_url: Url) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let query_client = query_client(grpc_url).await?;
+    let chains = query_client.list_chains().try_collect::<Vec<_>>().await?;
+
+    Ok(chains.into_iter().map(|chain| chain.name).collect())
 }
 
 pub async fn register_daemon_to_organization(
@@ -121,27 +160,77 @@ pub async fn register_daemon_to_organization(
                         if errors[0].message.contains("Daemon not found") {
                             err = "Agent not found".to_string();
                         }
-                        println!("Error register agent to the organization: {:?}", err);
-                        println!("Retrying...");
+                        eprintln!("Error register agent to the organization: {:?}", err);
+                        status!("Retrying...");
                         std::thread::sleep(std::time::Duration::from_secs(1));
                         continue;
                     }
                 }
                 if status.is_success() {
-                    println!("Agent successfully registered to the organization.");
+                    status!("Agent successfully registered to the organization.");
                     return Ok(());
                 }
                 std::thread::sleep(std::time::Duration::from_secs(1));
-                println!("Error register agent to the organization. Retrying...");
+                eprintln!("Error register agent to the organization. Retrying...");
             }
             Err(e) => {
-                println!("Error register agent to the organization: {}", e);
+                eprintln!("Error register agent to the organization: {}", e);
                 return Err(e);
             }
         };
     }
 }
 
+/// Organization the logged in user belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Organization {
+    pub id: String,
+    pub name: String,
+}
+
+/// Lists the organizations of the user of `token`.
+pub async fn list_organizations(
+    graphql_url: &str,
+    token: &str,
+) -> Result<Vec<Organization>, Box<dyn std::error::Error>> {
+    #[derive(Deserialize)]
+    struct Data {
+        organizations: Vec<Organization>,
+    }
+
+    #[derive(Deserialize)]
+    struct OrganizationsResponse {
+        data: Option<Data>,
+        errors: Option<Vec<errors::Error>>,
+    }
+
+    let query = r#"
+    query {
+        organizations {
+          id
+          name
+        }
+      }
+    "#;
+    let response = reqwest::Client::new()
+        .post(graphql_url)
+        .json(&json!({ "query": query }))
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await?
+        .json::<OrganizationsResponse>()
+        .await?;
+
+    if let Some(error) = response.errors.unwrap_or_default().into_iter().next() {
+        return Err(error.message.into());
+    }
+    Ok(response
+        .data
+        .map(|data| data.organizations)
+        .unwrap_or_default())
+}
+
 pub async fn ping_graphql(
     graphql_url: &str,
     token: &str,
@@ -1,27 +1,41 @@
-use cred_store::CredStore;
+use serde::Serialize;
 
 use crate::client::{ping_graphql, register_daemon_to_organization};
+use crate::errors::CliError;
+use crate::output::{Render, Table};
+use crate::status;
+
+/// Agent assigned to an organization by `agent assign`.
+#[derive(Debug, Serialize)]
+pub struct AssignResult {
+    pub agent_id: String,
+    pub organization_id: String,
+}
+
+impl Render for AssignResult {
+    fn table(&self) -> Table {
+        Table::new(vec!["AGENT ID", "ORGANIZATION ID"])
+            .row(vec![self.agent_id.clone(), self.organization_id.clone()])
+    }
+}
 
 pub async fn assign_to_organization(
     graphql_url: String,
     daemon_id: String,
     organization_id: String,
-    cred_store: &impl CredStore,
-) -> Result<(), reqwest::Error> {
-    let token = cred_store
-        .get("access_token")
-        .expect("access_token required");
+    token: &str,
+) -> Result<(), CliError> {
     // Ping graphql
-    let resp = ping_graphql(&graphql_url, token).await;
-
-    if resp.is_err() {
-        println!("Error ping graphql");
-        return Err(resp.err().unwrap());
+    if let Err(e) = ping_graphql(&graphql_url, token).await {
+        return Err(CliError::Graphql(format!(
+            "couldn't reach {}: {}",
+            graphql_url, e
+        )));
     }
 
     // Assign agent to organization
 
-    println!("Assign agent to organization: {}", organization_id);
+    status!("Assign agent to organization: {}", organization_id);
 
     match register_daemon_to_organization(
         graphql_url.as_str(),
@@ -32,6 +46,9 @@ pub async fn assign_to_organization(
     .await
     {
         Ok(_) => Ok(()),
-        Err(e) => Err(e),
+        Err(e) => Err(CliError::Graphql(format!(
+            "couldn't assign agent {} to organization {}: {}",
+            daemon_id, organization_id, e
+        ))),
     }
 }
@@ -0,0 +1,184 @@
+use inline_colorization::{color_green, color_reset, color_yellow};
+
+use crate::artifact::{resolve_wasm_file, WASM_TARGETS};
+use crate::errors::CliError;
+use crate::status;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// cargo-component version agents are built with.
+pub const CARGO_COMPONENT_VERSION: &str = "0.11.0";
+/// Target agents are compiled to by the supported cargo-component version.
+pub const WASM_TARGET: &str = "wasm32-wasi";
+
+/// Builds the agent in `dir_path` with `cargo-component build --release`.
+///
+/// The agent is built for `target` when set, it must be one of [`WASM_TARGETS`]. The build output
+/// is streamed to the terminal. Returns the path of the built wasm file.
+pub fn build_agent(
+    dir_path: &Path,
+    target: Option<&str>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Some(target) = target {
+        check_target_name(target)?;
+    }
+    check_cargo_component()?;
+    check_wasm_target(target)?;
+
+    status!(
+        "Building agent: {color_green}{}{color_reset}",
+        dir_path.display()
+    );
+    let mut command = Command::new("cargo-component");
+    command.args(["build", "--release"]).current_dir(dir_path);
+    if let Some(target) = target {
+        command.args(["--target", target]);
+    }
+    let status = command.status()?;
+    if !status.success() {
+        return Err(format!("cargo-component build failed: {}", status).into());
+    }
+
+    let wasm_file = resolve_wasm_file(dir_path)?;
+
+    status!(
+        "Agent built: {color_green}{}{color_reset}",
+        wasm_file.display()
+    );
+
+    Ok(wasm_file)
+}
+
+fn check_cargo_component() -> Result<(), Box<dyn std::error::Error>> {
+    let output = match Command::new("cargo-component").arg("--version").output() {
+        Ok(output) => output,
+        Err(_) => {
+            return Err(format!(
+                "cargo-component not found, install it with: `cargo install cargo-component@={} --locked`",
+                CARGO_COMPONENT_VERSION
+            )
+            .into())
+        }
+    };
+
+    let version = parse_version(&String::from_utf8_lossy(&output.stdout));
+    match version {
+        Some(version) if version == CARGO_COMPONENT_VERSION => (),
+        Some(version) => status!(
+            "{color_yellow}Warning{color_reset}: cargo-component {} is installed, agents are built with {}",
+            version, CARGO_COMPONENT_VERSION
+        ),
+        None => status!(
+            "{color_yellow}Warning{color_reset}: couldn't detect the cargo-component version"
+        ),
+    }
+
+    Ok(())
+}
+
+/// Checks that `target` is one of the targets the built artifact is resolved for.
+fn check_target_name(target: &str) -> Result<(), CliError> {
+    if WASM_TARGETS.contains(&target) {
+        return Ok(());
+    }
+
+    Err(CliError::Wasm(format!(
+        "unsupported target {}, agents are built for {}",
+        target,
+        WASM_TARGETS.join(", ")
+    )))
+}
+
+/// Checks that `target`, or any of [`WASM_TARGETS`] when unset, is installed with rustup.
+fn check_wasm_target(target: Option<&str>) -> Result<(), CliError> {
+    let output = match Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .output()
+    {
+        Ok(output) => output,
+        Err(_) => {
+            status!(
+                "{color_yellow}Warning{color_reset}: rustup not found, can't check the {} target",
+                target.unwrap_or(WASM_TARGET)
+            );
+            return Ok(());
+        }
+    };
+
+    let installed = String::from_utf8_lossy(&output.stdout);
+    if target_installed(&installed, target) {
+        return Ok(());
+    }
+
+    Err(CliError::Wasm(match target {
+        Some(target) => format!(
+            "{} target is not installed, add it with: `rustup target add {}`",
+            target, target
+        ),
+        None => format!(
+            "none of the {} targets is installed, add one with: `rustup target add {}`",
+            WASM_TARGETS.join(", "),
+            WASM_TARGET
+        ),
+    }))
+}
+
+/// Returns whether `rustup target list --installed` output lists `target`, or any of
+/// [`WASM_TARGETS`] when unset.
+fn target_installed(installed: &str, target: Option<&str>) -> bool {
+    installed.lines().map(str::trim).any(|name| match target {
+        Some(target) => name == target,
+        None => WASM_TARGETS.contains(&name),
+    })
+}
+
+/// Extracts the version from `cargo-component --version` output.
+fn parse_version(output: &str) -> Option<&str> {
+    output.split_whitespace().find(|token| {
+        token.split('.').count() == 3
+            && token
+                .split('.')
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("cargo-component 0.11.0 (wasi:040ec92)\n"),
+            Some("0.11.0")
+        );
+        assert_eq!(parse_version("cargo-component 0.13.2"), Some("0.13.2"));
+        assert_eq!(parse_version("cargo-component"), None);
+    }
+
+    #[test]
+    fn test_check_target_name() {
+        for target in WASM_TARGETS {
+            assert!(check_target_name(target).is_ok());
+        }
+
+        let error = check_target_name("wasm32-unknown-unknown").unwrap_err();
+        assert!(matches!(error, CliError::Wasm(_)));
+        assert_eq!(
+            error.to_string(),
+            "invalid agent wasm: unsupported target wasm32-unknown-unknown, agents are built for wasm32-wasip2, wasm32-wasip1, wasm32-wasi"
+        );
+    }
+
+    #[test]
+    fn test_target_installed() {
+        let installed = "wasm32-wasip1\nx86_64-unknown-linux-gnu\n";
+
+        assert!(target_installed(installed, None));
+        assert!(target_installed(installed, Some("wasm32-wasip1")));
+        assert!(!target_installed(installed, Some("wasm32-wasip2")));
+        assert!(!target_installed("x86_64-unknown-linux-gnu\n", None));
+    }
+}
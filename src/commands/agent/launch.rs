@@ -1,11 +1,14 @@
-use crate::client::message_client;
+use crate::client::{grpc_url, message_client};
+use crate::commands::agent::publish::PublishedAgent;
 use crate::daemon_builder::{build_daemon_parameters, check_supported_chains};
+use crate::errors::CliError;
+use crate::output::Progress;
+use crate::status;
 use crate::{input::input_user_params, manifest::read_manifest_file};
 use inline_colorization::{color_green, color_reset};
-use spinners::{Spinner, Spinners};
 use std::{collections::HashMap, path::Path};
-use url::Url;
 
+/// Launches an agent from registered metadata on `chain_name`.
 pub async fn launch_agent(
     metadata_id: String,
     grpc: String,
@@ -14,47 +17,50 @@ pub async fn launch_agent(
     dir_path: &Path,
     gas_limit: u64,
     chain_id: String,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let manifest = read_manifest_file(dir_path).expect("Manifest file not found");
+) -> Result<PublishedAgent, Box<dyn std::error::Error>> {
+    let manifest = read_manifest_file(dir_path).ok_or_else(|| {
+        CliError::Manifest(format!("manifest.yaml not found in {}", dir_path.display()))
+    })?;
 
     if !check_supported_chains(&manifest.supported_chains, &chain_name) {
-        eprintln!(
-            "Unsupported chain, please use one of the following: {:?}",
+        return Err(CliError::Manifest(format!(
+            "unsupported chain, please use one of the following: {:?}",
             manifest.supported_chains
-        );
-        std::process::exit(1);
+        ))
+        .into());
     }
 
-    let message_client =
-        message_client(prkey, &grpc.parse::<Url>().unwrap(), gas_limit, chain_id).await;
+    let message_client = message_client(prkey, &grpc_url(&grpc)?, gas_limit, chain_id).await?;
     let mut user_params: HashMap<String, String> = HashMap::new();
     if let Some(manifest_params) = &manifest.parameters {
-        input_user_params(manifest_params, &mut user_params);
+        input_user_params(manifest_params, &chain_name, &mut user_params)
+            .map_err(|e| CliError::Config(e.to_string()))?;
     }
     let daemon_parameters =
-        build_daemon_parameters(manifest.parameters, user_params, chain_name.clone());
+        build_daemon_parameters(manifest.parameters, user_params.clone(), chain_name.clone());
 
-    let mut sp = Spinner::new(Spinners::Triangle, "Publishing agent".into());
+    let mut progress = Progress::start("Publishing agent");
 
     let daemon = match message_client
         .register_daemon(metadata_id, chain_name.clone(), daemon_parameters, None)
         .await
     {
-        Ok(daemon) => Some(daemon),
+        Ok(daemon) => daemon,
         Err(e) => {
-            sp.stop();
-            println!("Error registering agent: {:?}", e);
-            None
+            progress.stop();
+            return Err(CliError::Chain(format!("couldn't register agent: {:?}", e)).into());
         }
     };
 
-    sp.stop();
+    progress.stop();
 
-    let daemon_id = daemon.unwrap().daemon_id;
+    status!("AgentId: {color_green}{}{color_reset}", daemon.daemon_id);
+    status!("Agent successfully registered");
 
-    println!();
-    println!("AgentId: {color_green}{}{color_reset}", daemon_id);
-    println!("Agent successfully registered");
-
-    Ok(daemon_id)
+    Ok(PublishedAgent {
+        chain_name,
+        daemon_id: daemon.daemon_id,
+        params: user_params,
+        tx_hash: Some(daemon.tx_hash).filter(|hash| !hash.is_empty()),
+    })
 }
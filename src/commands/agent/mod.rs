@@ -1,5 +1,8 @@
 pub mod assign;
+pub mod build;
 pub mod launch;
 pub mod new;
 pub mod publish;
+pub mod test;
 pub mod unregister;
+pub mod upgrade;
@@ -1,41 +1,469 @@
-#[cfg(feature = "no-ssl")]
+#[cfg(feature = "git-templates")]
 use cargo_generate::{generate, GenerateArgs, TemplatePath, Vcs};
+use include_dir::{include_dir, Dir, DirEntry};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
-#[allow(dead_code)]
-const GIT_TEMPLATE_PATH: &str = "https://github.com/Mamoru-Foundation/mamoru-wit-agent-templat.git";
+use crate::input::select_user_input;
+use crate::output::{Render, Table};
+use crate::status;
 
-/// This function creates a new agent with the given name.
-///
-/// It uses the `cargo_generate` crate to generate a new project from a git template.
-/// The template is specified by the `GIT_TEMPLATE_PATH` constant.
-///
-/// # Arguments
-///
-/// * `name` - A string that holds the name of the new agent.
-///
-/// # Examples
-///
-/// ```sh
-/// cargo generate --git https://github.com/Mamoru-Foundation/mamoru-wit-agent-templat.git --name my-project
-/// ```
-///
-/// # Panics
+/// Mamoru agent world, copied to `wit/agent.wit` of agents generated from built-in templates.
+const AGENT_WIT: &str = include_str!("../../../wit/agent.wit");
+
+/// Suffix of template files that would otherwise be picked up by tools, like `Cargo.toml.tmpl`.
+const TEMPLATE_SUFFIX: &str = ".tmpl";
+
+/// Built-in templates, `templates/<name>` of the repository, embedded in the binary.
+static TEMPLATES_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/templates");
+
+/// Template bundled with the CLI, usable offline.
+pub struct BuiltinTemplate {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Prefix of the chain names the template is meant for.
+    pub chain_prefixes: &'static [&'static str],
+}
+
+pub static BUILTIN_TEMPLATES: [BuiltinTemplate; 3] = [
+    BuiltinTemplate {
+        name: "evm",
+        description: "EVM chains, reports transfers above a threshold",
+        chain_prefixes: &[],
+    },
+    BuiltinTemplate {
+        name: "sui",
+        description: "Sui, reports failed transactions",
+        chain_prefixes: &["SUI"],
+    },
+    BuiltinTemplate {
+        name: "aptos",
+        description: "Aptos, reports failed transactions",
+        chain_prefixes: &["APTOS"],
+    },
+];
+
+/// Where a new agent is generated from, see [`TemplateSource::parse`].
+#[derive(Debug, PartialEq)]
+pub enum TemplateSource {
+    Builtin(&'static str),
+    Git(String),
+    Path(PathBuf),
+}
+
+impl TemplateSource {
+    /// Parses `--template`: a built-in template name, a git URL or a local directory.
+    pub fn parse(template: &str) -> TemplateSource {
+        if let Some(builtin) = BUILTIN_TEMPLATES.iter().find(|t| t.name == template) {
+            return TemplateSource::Builtin(builtin.name);
+        }
+
+        let is_git_url = ["https://", "http://", "ssh://", "git@"]
+            .iter()
+            .any(|prefix| template.starts_with(prefix))
+            || template.ends_with(".git");
+        if is_git_url {
+            TemplateSource::Git(template.to_string())
+        } else {
+            TemplateSource::Path(PathBuf::from(template))
+        }
+    }
+}
+
+/// Values substituted for `{{agent_name}}`, `{{crate_name}}`, `{{description}}` and `{{chains}}`
+/// in template files, and written to the generated `manifest.yaml`.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVariables {
+    pub agent_name: String,
+    pub crate_name: String,
+    pub description: Option<String>,
+    pub chains: Vec<String>,
+}
+
+impl TemplateVariables {
+    pub fn new(agent_name: &str, description: Option<String>, chains: Vec<String>) -> Self {
+        TemplateVariables {
+            agent_name: agent_name.to_string(),
+            crate_name: crate_name(agent_name),
+            description,
+            chains,
+        }
+    }
+
+    fn render(&self, content: &str) -> String {
+        content
+            .replace("{{agent_name}}", &self.agent_name)
+            .replace("{{crate_name}}", &self.crate_name)
+            .replace("{{description}}", self.description.as_deref().unwrap_or(""))
+            .replace("{{chains}}", &self.chains.join(","))
+    }
+}
+
+/// Creates a new agent named `name` in the current directory.
 ///
-/// This function will panic if the generation of the new agent fails for any reason.
-#[cfg(feature = "no-ssl")]
-pub fn create_new_agent(name: String) {
-    println!("Creating new agent at {}", name);
+/// `template` is a built-in template name, a git URL (needs the `git-templates` feature) or a
+/// local template directory. Without it the template is picked from the first chain, or asked
+/// for. The agent name, description and chains are written to the generated `manifest.yaml`.
+pub fn create_new_agent(
+    name: &str,
+    template: Option<&str>,
+    description: Option<String>,
+    chains: Vec<String>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let variables = TemplateVariables::new(name, description, chains);
+    if variables.crate_name.is_empty() {
+        return Err(format!("invalid agent name: {}", name).into());
+    }
+
+    let destination = std::env::current_dir()?.join(&variables.crate_name);
+    if destination.exists() {
+        return Err(format!("{} already exists", destination.display()).into());
+    }
+
+    let source = match template {
+        Some(template) => TemplateSource::parse(template),
+        None => TemplateSource::Builtin(default_template(&variables.chains)?),
+    };
+    status!("Creating new agent at {}", destination.display());
+
+    match &source {
+        TemplateSource::Builtin(name) => write_builtin_template(name, &destination, &variables)?,
+        TemplateSource::Path(path) => {
+            if !path.is_dir() {
+                return Err(format!(
+                    "template {} is not a built-in template ({}), a git URL or a directory",
+                    path.display(),
+                    builtin_template_names().join(", ")
+                )
+                .into());
+            }
+            copy_template_dir(path, &destination, &variables)?;
+        }
+        TemplateSource::Git(url) => generate_from_git(url, &destination, &variables)?,
+    }
+
+    apply_manifest_variables(&destination, &variables)?;
+
+    status!("Generated agent at {}", destination.display());
+    Ok(destination)
+}
+
+pub fn builtin_template_names() -> Vec<&'static str> {
+    BUILTIN_TEMPLATES.iter().map(|t| t.name).collect()
+}
+
+/// Built-in template listed by `agent new --list-templates`.
+#[derive(Debug, Serialize)]
+pub struct TemplateInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+impl Render for [TemplateInfo] {
+    fn table(&self) -> Table {
+        self.iter().fold(
+            Table::new(vec!["NAME", "DESCRIPTION"]),
+            |table, template| {
+                table.row(vec![
+                    template.name.to_string(),
+                    template.description.to_string(),
+                ])
+            },
+        )
+    }
+}
+
+/// Lists the built-in templates.
+pub fn templates() -> Vec<TemplateInfo> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .map(|template| TemplateInfo {
+            name: template.name,
+            description: template.description,
+        })
+        .collect()
+}
+
+/// Picks the template matching the first chain, asks when no chain is given.
+fn default_template(chains: &[String]) -> Result<&'static str, dialoguer::Error> {
+    match chains.first() {
+        Some(chain) => Ok(template_for_chain(chain)),
+        None => {
+            status!("Select a template:");
+            let items = BUILTIN_TEMPLATES
+                .iter()
+                .map(|t| format!("{} - {}", t.name, t.description))
+                .collect();
+            Ok(BUILTIN_TEMPLATES[select_user_input(items)?].name)
+        }
+    }
+}
+
+/// Returns the built-in template for `chain`, EVM for chains without a dedicated one.
+fn template_for_chain(chain: &str) -> &'static str {
+    let chain = chain.to_uppercase();
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|t| {
+            t.chain_prefixes
+                .iter()
+                .any(|prefix| chain.starts_with(prefix))
+        })
+        .map_or("evm", |t| t.name)
+}
+
+/// Turns the agent name into a cargo package name, e.g. `My Agent` into `my-agent`.
+fn crate_name(name: &str) -> String {
+    let name = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+
+    name.split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Writes the embedded template `name` and the agent world to `destination`.
+fn write_builtin_template(
+    name: &str,
+    destination: &Path,
+    variables: &TemplateVariables,
+) -> Result<(), std::io::Error> {
+    let template = TEMPLATES_DIR.get_dir(name).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("unknown built-in template {}", name),
+        )
+    })?;
+    write_template_dir(template, template.path(), destination, variables)?;
+
+    let wit_dir = destination.join("wit");
+    fs::create_dir_all(&wit_dir)?;
+    fs::write(wit_dir.join("agent.wit"), AGENT_WIT)
+}
+
+/// Writes the files of an embedded template directory like [`copy_template_dir`] does.
+fn write_template_dir(
+    dir: &Dir,
+    root: &Path,
+    destination: &Path,
+    variables: &TemplateVariables,
+) -> Result<(), std::io::Error> {
+    for entry in dir.entries() {
+        match entry {
+            DirEntry::Dir(dir) => write_template_dir(dir, root, destination, variables)?,
+            DirEntry::File(file) => {
+                let relative = file.path().strip_prefix(root).unwrap_or(file.path());
+                let file_name = relative.to_string_lossy();
+                let target = destination.join(
+                    file_name
+                        .strip_suffix(TEMPLATE_SUFFIX)
+                        .unwrap_or(&file_name),
+                );
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                match file.contents_utf8() {
+                    Some(text) => fs::write(target, variables.render(text))?,
+                    None => fs::write(target, file.contents())?,
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies a local template, rendering text files and dropping the `.tmpl` suffix.
+fn copy_template_dir(
+    source: &Path,
+    destination: &Path,
+    variables: &TemplateVariables,
+) -> Result<(), std::io::Error> {
+    fs::create_dir_all(destination)?;
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name == ".git" || file_name == "target" {
+            continue;
+        }
+
+        let target = destination.join(
+            file_name
+                .strip_suffix(TEMPLATE_SUFFIX)
+                .unwrap_or(file_name.as_str()),
+        );
+        if entry.file_type()?.is_dir() {
+            copy_template_dir(&entry.path(), &target, variables)?;
+        } else {
+            let content = fs::read(entry.path())?;
+            match String::from_utf8(content) {
+                Ok(text) => fs::write(target, variables.render(&text))?,
+                Err(e) => fs::write(target, e.into_bytes())?,
+            }
+        }
+    }
+
+    Ok(())
+}
 
+#[cfg(feature = "git-templates")]
+fn generate_from_git(
+    url: &str,
+    destination: &Path,
+    variables: &TemplateVariables,
+) -> Result<(), Box<dyn std::error::Error>> {
     let agent_args = GenerateArgs {
-        name: Some(name),
+        name: Some(variables.crate_name.clone()),
+        destination: destination.parent().map(Path::to_path_buf),
         vcs: Some(Vcs::Git),
         template_path: TemplatePath {
-            git: Some(GIT_TEMPLATE_PATH.to_string()),
+            git: Some(url.to_string()),
             ..TemplatePath::default()
         },
+        define: vec![
+            format!("agent_name={}", variables.agent_name),
+            format!(
+                "description={}",
+                variables.description.as_deref().unwrap_or("")
+            ),
+            format!("chains={}", variables.chains.join(",")),
+        ],
         ..GenerateArgs::default()
     };
 
-    let path = generate(agent_args).expect("something went wrong!");
-    println!("Generated agent at {:?}", path.display());
+    generate(agent_args)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "git-templates"))]
+fn generate_from_git(
+    url: &str,
+    _destination: &Path,
+    _variables: &TemplateVariables,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err(format!(
+        "can't use git template {}, the CLI was built without the `git-templates` feature. Use a built-in template ({}) or a local directory",
+        url,
+        builtin_template_names().join(", ")
+    )
+    .into())
+}
+
+/// Writes the agent name, description and chains to the generated `manifest.yaml`.
+fn apply_manifest_variables(
+    dir_path: &Path,
+    variables: &TemplateVariables,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest_path = dir_path.join("manifest.yaml");
+    if !manifest_path.exists() {
+        status!("Template has no manifest.yaml, skipping manifest variables");
+        return Ok(());
+    }
+
+    let mut manifest: serde_yaml::Value =
+        serde_yaml::from_str(&fs::read_to_string(&manifest_path)?)?;
+    let mapping = manifest
+        .as_mapping_mut()
+        .ok_or("manifest.yaml is not a mapping")?;
+
+    mapping.insert("name".into(), variables.agent_name.clone().into());
+    if let Some(description) = &variables.description {
+        mapping.insert("description".into(), description.clone().into());
+    }
+    if !variables.chains.is_empty() {
+        mapping.insert(
+            "chains".into(),
+            serde_yaml::Value::Sequence(
+                variables
+                    .chains
+                    .iter()
+                    .map(|chain| chain.clone().into())
+                    .collect(),
+            ),
+        );
+    }
+
+    fs::write(manifest_path, serde_yaml::to_string(&manifest)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::read_manifest_file;
+    use sealed_test::prelude::*;
+
+    #[test]
+    fn test_template_source() {
+        assert_eq!(TemplateSource::parse("sui"), TemplateSource::Builtin("sui"));
+        assert_eq!(
+            TemplateSource::parse("https://github.com/org/template"),
+            TemplateSource::Git("https://github.com/org/template".to_string())
+        );
+        assert_eq!(
+            TemplateSource::parse("git@github.com:org/template.git"),
+            TemplateSource::Git("git@github.com:org/template.git".to_string())
+        );
+        assert_eq!(
+            TemplateSource::parse("../my-template"),
+            TemplateSource::Path(PathBuf::from("../my-template"))
+        );
+    }
+
+    #[test]
+    fn test_template_for_chain() {
+        assert_eq!(template_for_chain("SUI_MAINNET"), "sui");
+        assert_eq!(template_for_chain("aptos_testnet"), "aptos");
+        assert_eq!(template_for_chain("ETH_MAINNET"), "evm");
+        assert_eq!(template_for_chain("BSC_MAINNET"), "evm");
+    }
+
+    #[test]
+    fn test_crate_name() {
+        assert_eq!(crate_name("My Agent"), "my-agent");
+        assert_eq!(crate_name(" sui--watcher_v2 "), "sui-watcher-v2");
+        assert_eq!(crate_name("!!"), "");
+    }
+
+    #[test]
+    fn test_builtin_templates_render() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let destination = tmp_dir.path().join("my-agent");
+        let variables = TemplateVariables::new(
+            "My Agent",
+            Some("Watches transfers".to_string()),
+            vec!["BSC_MAINNET".to_string()],
+        );
+
+        for template in builtin_template_names() {
+            let destination = destination.join(template);
+            write_builtin_template(template, &destination, &variables).unwrap();
+            apply_manifest_variables(&destination, &variables).unwrap();
+
+            let manifest = read_manifest_file(&destination).unwrap();
+            assert_eq!(manifest.name, "My Agent");
+            assert_eq!(manifest.description, "Watches transfers");
+            assert_eq!(manifest.supported_chains, vec!["BSC_MAINNET".to_string()]);
+
+            let cargo_toml = fs::read_to_string(destination.join("Cargo.toml")).unwrap();
+            assert!(cargo_toml.contains("name = \"my-agent\""));
+            assert!(destination.join("wit/agent.wit").exists());
+        }
+
+        tmp_dir.close().unwrap();
+    }
 }
@@ -1,163 +1,400 @@
-use crate::client::query_client;
+use crate::artifact::resolve_wasm_file;
+use crate::client::{grpc_url, query_client, signer_address};
+use crate::completion;
+use crate::content_hash::{agent_content_hash, wasm_hash};
+use crate::deployments::Deployments;
+use crate::errors::CliError;
+use crate::output::{Progress, Render, Table};
+use crate::status;
+use crate::wasm_optimize::optimize_wasm;
+use crate::wasm_validation::validate_agent_wasm;
 use crate::{
     client::message_client,
     daemon_builder::{
         build_daemon_metadata_request, build_daemon_parameters, check_supported_chains,
     },
-    input::{input_user_params, select_user_input},
-    manifest::read_manifest_file,
+    input::input_user_params,
+    manifest::{read_manifest_file, Manifest},
+};
+use inline_colorization::{color_green, color_red, color_reset};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    time::Duration,
 };
-use inline_colorization::{color_green, color_reset};
-use spinners::{Spinner, Spinners};
-use std::{collections::HashMap, fs, path::Path, time::Duration};
 use tokio::time;
-use url::Url;
 
 use futures::TryStreamExt;
 
-/// Publishes an agent to a specified chain.
+/// Chain to launch the agent on, with the user parameters for that chain.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PublishTarget {
+    pub chain_name: String,
+    pub params: HashMap<String, String>,
+}
+
+/// Agent registered on a single chain during `publish`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PublishedAgent {
+    pub chain_name: String,
+    pub daemon_id: String,
+    pub params: HashMap<String, String>,
+    /// Hash of the transaction that registered the agent.
+    pub tx_hash: Option<String>,
+}
+
+/// Chain where the agent couldn't be registered.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FailedChain {
+    pub chain_name: String,
+    pub error: String,
+}
+
+/// Result of publishing one metadata to one or more chains.
+#[derive(Debug, Default, Serialize)]
+pub struct PublishResult {
+    pub metadata_id: String,
+    /// ID of the validation chain the agents were registered on.
+    pub chain_id: String,
+    /// Address of the account that signed the transactions.
+    pub signer: String,
+    pub wasm_hash: String,
+    pub content_hash: String,
+    pub agents: Vec<PublishedAgent>,
+    pub failed: Vec<FailedChain>,
+}
+
+impl Render for PublishResult {
+    /// One row with the agent ID, or the error, for every chain.
+    fn table(&self) -> Table {
+        summary_rows(self).into_iter().fold(
+            Table::new(vec!["CHAIN", "METADATA ID", "AGENT ID"]),
+            |table, (chain, agent)| table.row(vec![chain, self.metadata_id.clone(), agent]),
+        )
+    }
+}
+
+/// Publishes an agent to the specified chains.
+///
+/// The wasm module is read from `wasm_file` when set, see [`read_wasm_file`]. With `optimize` the
+/// module is shrunk with [`optimize_wasm`] before it's registered.
+///
+/// This function reads a manifest file, collects user parameters for every chain, registers
+/// daemon metadata once, and finally registers one daemon per chain. It uses the `message_client`
+/// to communicate with the chain. When `all_supported` is set, the agent is launched on every
+/// chain listed in the manifest that is also supported by the validation chain. Parameters in
+/// `known_params` are used as they are, only the other ones are asked for.
 ///
-/// This function reads a manifest file, collects user parameters, registers daemon metadata,
-/// and finally registers the daemon itself. It uses the `message_client` to communicate with the chain.
+/// With `reuse_metadata` the metadata registration is skipped when the deployment state of the
+/// agent directory records metadata of the same `profile`, validation chain and signer with the
+/// same wasm and manifest content hash, see [`Deployments::find_metadata_id`].
+#[allow(clippy::too_many_arguments)]
 pub async fn publish_agent(
     grpc: String,
     prkey: String,
-    chain_name: String,
+    chain_names: Vec<String>,
+    all_supported: bool,
+    known_params: &HashMap<String, String>,
+    reuse_metadata: bool,
+    profile: &str,
     dir_path: &Path,
+    wasm_file: Option<&Path>,
+    optimize: bool,
     gas_limit: u64,
     chain_id: String,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let manifest = read_manifest_file(dir_path).expect("Manifest file not found");
+) -> Result<PublishResult, Box<dyn std::error::Error>> {
+    let manifest = read_manifest_file(dir_path).ok_or_else(|| {
+        CliError::Manifest(format!("manifest.yaml not found in {}", dir_path.display()))
+    })?;
 
-    println!(
-        "Publishing agent to chain: {color_green}{}{color_reset}",
-        chain_name
-    );
-
-    if !check_supported_chains(&manifest.supported_chains, &chain_name) {
-        eprintln!(
-            "Unsupported chain, manifest support only: {:?}",
-            manifest.supported_chains
-        );
-        std::process::exit(1);
-    }
-    let query_client = query_client(grpc.parse::<Url>().unwrap()).await;
+    let query_client = query_client(grpc_url(&grpc)?).await?;
     let supported_vc_chains_data = query_client
         .list_chains()
         .try_collect::<Vec<_>>()
         .await
-        .unwrap();
+        .map_err(|e| CliError::Chain(format!("couldn't list chains: {}", e)))?;
     let mut supported_vc_chains: Vec<String> = vec![];
     for chain_data in supported_vc_chains_data {
         supported_vc_chains.push(chain_data.name);
     }
+    completion::update_history(|history| history.remember_chains(&supported_vc_chains));
 
-    if !check_supported_chains(&supported_vc_chains, &chain_name) {
-        eprintln!(
-            "Unsupported chain, please use one of the following: {:?}",
-            supported_vc_chains
-        );
-        std::process::exit(1);
+    let chain_names = if all_supported {
+        manifest
+            .supported_chains
+            .iter()
+            .filter(|chain_name| check_supported_chains(&supported_vc_chains, chain_name))
+            .cloned()
+            .collect::<Vec<_>>()
+    } else {
+        let mut seen = HashSet::new();
+        let mut chain_names = chain_names;
+        chain_names.retain(|chain_name| seen.insert(chain_name.clone()));
+        for chain_name in &chain_names {
+            if !check_supported_chains(&manifest.supported_chains, chain_name) {
+                return Err(CliError::Manifest(format!(
+                    "unsupported chain {}, manifest support only: {:?}",
+                    chain_name, manifest.supported_chains
+                ))
+                .into());
+            }
+            if !check_supported_chains(&supported_vc_chains, chain_name) {
+                return Err(CliError::Chain(format!(
+                    "unsupported chain {}, please use one of the following: {:?}",
+                    chain_name, supported_vc_chains
+                ))
+                .into());
+            }
+        }
+        chain_names
+    };
+
+    if chain_names.is_empty() {
+        return Err(CliError::Chain(
+            "none of the manifest chains are supported by the validation chain".to_string(),
+        )
+        .into());
     }
 
-    let mut user_params: HashMap<String, String> = HashMap::new();
-    if let Some(manifest_params) = &manifest.parameters {
-        input_user_params(manifest_params, &mut user_params);
+    status!(
+        "Publishing agent to chains: {color_green}{}{color_reset}",
+        chain_names.join(", ")
+    );
+
+    let mut targets: Vec<PublishTarget> = vec![];
+    for chain_name in chain_names {
+        let mut user_params = known_params.clone();
+        if let Some(manifest_params) = &manifest.parameters {
+            input_user_params(manifest_params, &chain_name, &mut user_params)
+                .map_err(|e| CliError::Config(e.to_string()))?;
+        }
+        targets.push(PublishTarget {
+            chain_name,
+            params: user_params,
+        });
     }
 
+    publish_to_chains(
+        grpc,
+        prkey,
+        &manifest,
+        targets,
+        reuse_metadata,
+        profile,
+        dir_path,
+        wasm_file,
+        optimize,
+        gas_limit,
+        chain_id,
+    )
+    .await
+}
+
+/// Registers (or reuses) the agent metadata and launches one agent per target.
+///
+/// Unlike [`publish_agent`] this doesn't ask for parameters or validate chain names.
+#[allow(clippy::too_many_arguments)]
+pub async fn publish_to_chains(
+    grpc: String,
+    prkey: String,
+    manifest: &Manifest,
+    targets: Vec<PublishTarget>,
+    reuse_metadata: bool,
+    profile: &str,
+    dir_path: &Path,
+    wasm_file: Option<&Path>,
+    optimize: bool,
+    gas_limit: u64,
+    chain_id: String,
+) -> Result<PublishResult, Box<dyn std::error::Error>> {
+    let signer = signer_address(&prkey)?;
     let message_client =
-        message_client(prkey, &grpc.parse::<Url>().unwrap(), gas_limit, chain_id).await;
-    let module_content = read_wasm_file(dir_path)?;
-    let request = build_daemon_metadata_request(&manifest, &module_content);
-
-    let mut sp = Spinner::new(Spinners::Triangle, "Publishing agent...".into());
-
-    let dm_response = match message_client.register_daemon_metadata(request).await {
-        Ok(response) => response,
-        Err(e) => {
-            sp.stop();
-            println!();
-            println!("Error registering agent metadata: {:?}", e);
-            return Err(Box::new(e));
-        }
+        message_client(prkey, &grpc_url(&grpc)?, gas_limit, chain_id.clone()).await?;
+    let mut module_content =
+        read_wasm_file(dir_path, wasm_file).map_err(|e| CliError::Wasm(e.to_string()))?;
+    if optimize {
+        let optimized =
+            optimize_wasm(&module_content).map_err(|e| CliError::Wasm(e.to_string()))?;
+        print_optimization(module_content.len(), &optimized);
+        module_content = optimized;
+    }
+    // hash the uploaded module, an optimized upload never matches a plain one
+    let content_hash = agent_content_hash(dir_path, &module_content)?;
+    let component =
+        validate_agent_wasm(&module_content).map_err(|e| CliError::Wasm(e.to_string()))?;
+    status!(
+        "Wasm component is valid: {} KiB, imports: {}, exports: {}",
+        module_content.len() / 1024,
+        component.imports.join(", "),
+        component.exports.join(", ")
+    );
+
+    let existing_metadata_id = if reuse_metadata {
+        Deployments::load(dir_path)?
+            .find_metadata_id(profile, &chain_id, &signer, &content_hash)
+            .map(|metadata_id| metadata_id.to_string())
+    } else {
+        None
     };
 
-    time::sleep(Duration::from_millis(1000)).await;
+    let daemon_metadata_id = match existing_metadata_id {
+        Some(daemon_metadata_id) => {
+            status!(
+                "Metadata with the same content already exists, reusing MetadataId: {color_green}{}{color_reset}",
+                daemon_metadata_id
+            );
+            daemon_metadata_id
+        }
+        None => {
+            let request = build_daemon_metadata_request(manifest, &module_content)?;
 
-    let daemon_metadata_id = dm_response.daemon_metadata_id;
-    println!();
-    println!(
-        "MetadataId: {color_green}{}{color_reset}",
-        daemon_metadata_id
-    );
+            let mut progress = Progress::start("Publishing agent...");
 
-    println!("Metadata successfully registered");
+            let dm_response = match message_client.register_daemon_metadata(request).await {
+                Ok(response) => response,
+                Err(e) => {
+                    progress.stop();
+                    return Err(CliError::Chain(format!(
+                        "couldn't register agent metadata: {:?}",
+                        e
+                    ))
+                    .into());
+                }
+            };
 
-    let daemon_parameters =
-        build_daemon_parameters(manifest.parameters, user_params, chain_name.clone());
-    let relay = None;
-    let daemon = match message_client
-        .register_daemon(
-            daemon_metadata_id,
-            chain_name.clone(),
-            daemon_parameters,
-            relay,
-        )
-        .await
-    {
-        Ok(daemon) => Some(daemon),
-        Err(e) => {
-            sp.stop();
-            println!("Error registering agent: {:?}", e);
-            None
+            time::sleep(Duration::from_millis(1000)).await;
+
+            let daemon_metadata_id = dm_response.daemon_metadata_id;
+            progress.stop();
+            status!(
+                "MetadataId: {color_green}{}{color_reset}",
+                daemon_metadata_id
+            );
+
+            status!("Metadata successfully registered");
+            daemon_metadata_id
         }
     };
 
+    let mut result = PublishResult {
+        metadata_id: daemon_metadata_id.clone(),
+        chain_id,
+        signer,
+        wasm_hash: wasm_hash(&module_content),
+        content_hash,
+        ..Default::default()
+    };
+
+    for PublishTarget { chain_name, params } in targets {
+        let daemon_parameters = build_daemon_parameters(
+            manifest.parameters.clone(),
+            params.clone(),
+            chain_name.clone(),
+        );
+        let relay = None;
+        let mut progress = Progress::start(format!("Launching agent on {}...", chain_name));
+        match message_client
+            .register_daemon(
+                daemon_metadata_id.clone(),
+                chain_name.clone(),
+                daemon_parameters,
+                relay,
+            )
+            .await
+        {
+            Ok(daemon) => {
+                progress.stop();
+                status!(
+                    "AgentId ({}): {color_green}{}{color_reset}",
+                    chain_name,
+                    daemon.daemon_id
+                );
+                result.agents.push(PublishedAgent {
+                    chain_name,
+                    daemon_id: daemon.daemon_id,
+                    params,
+                    tx_hash: Some(daemon.tx_hash).filter(|hash| !hash.is_empty()),
+                });
+            }
+            Err(e) => {
+                progress.stop();
+                eprintln!("Error registering agent on {}: {:?}", chain_name, e);
+                result.failed.push(FailedChain {
+                    chain_name,
+                    error: format!("{:?}", e),
+                });
+            }
+        };
+    }
+
     time::sleep(Duration::from_millis(2000)).await;
-    sp.stop_with_message("Completed".into());
-    let daemon_id = daemon.unwrap().daemon_id;
-    println!();
-    println!("AgentId: {color_green}{}{color_reset}", daemon_id);
-    println!("Agent successfully registered");
 
-    Ok(daemon_id)
+    if result.agents.is_empty() {
+        return Err(CliError::Chain("agent wasn't registered on any chain".to_string()).into());
+    }
+
+    status!("Agent successfully registered");
+
+    Ok(result)
 }
 
-fn read_wasm_file(dir_path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let wasm_dir_path = dir_path.join("target/wasm32-wasi/release/");
-    let wasm_files = fs::read_dir(wasm_dir_path.clone())?
-        .map(|entry| entry.unwrap().path())
-        .filter(|path| path.extension().map_or(false, |ext| ext == "wasm"))
-        .collect::<Vec<_>>();
-
-    match wasm_files.len() {
-        0 => Err("no Wasm file found".into()),
-        1 => {
-            let wasm_file = wasm_files.first().expect("Wasm file not found");
-            let wasm_file_path = wasm_file.canonicalize()?;
-            println!("wasm_file: {:?}", wasm_file_path);
-            Ok(std::fs::read(wasm_file_path).expect(
-                "Error reading Wasm file, use command for build agent: `cargo-component build -r`",
-            ))
-        }
-        _ => {
-            let items = wasm_files
-                .iter()
-                .map(|path| path.file_name().unwrap().to_str().unwrap().to_string())
-                .collect();
-            let index = select_user_input(items);
-            let wasm_file = wasm_files.get(index).expect("Wasm file not found");
-            let wasm_file_path = wasm_file.canonicalize()?;
-            Ok(std::fs::read(wasm_file_path).expect(
-                "Error reading Wasm file, use command for build agent: `cargo-component build -r`",
-            ))
-        }
-    }
+fn print_optimization(original_size: usize, optimized: &[u8]) {
+    let saved = original_size.saturating_sub(optimized.len());
+    status!(
+        "Wasm optimized: {} KiB -> {color_green}{} KiB{color_reset} ({:.1}% smaller)",
+        original_size / 1024,
+        optimized.len() / 1024,
+        saved as f64 * 100.0 / original_size.max(1) as f64
+    );
+    status!(
+        "Module hash: {color_green}{}{color_reset}",
+        wasm_hash(optimized)
+    );
+}
+
+fn summary_rows(result: &PublishResult) -> Vec<(String, String)> {
+    result
+        .agents
+        .iter()
+        .map(|agent| (agent.chain_name.clone(), agent.daemon_id.clone()))
+        .chain(result.failed.iter().map(|failed| {
+            (
+                failed.chain_name.clone(),
+                format!("{color_red}FAILED{color_reset}: {}", failed.error),
+            )
+        }))
+        .collect()
+}
+
+/// Reads the agent wasm module.
+///
+/// `wasm_file` takes precedence, otherwise the artifact is resolved with `cargo metadata`.
+pub fn read_wasm_file(
+    dir_path: &Path,
+    wasm_file: Option<&Path>,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let wasm_file_path = match wasm_file {
+        Some(wasm_file) => wasm_file.canonicalize()?,
+        None => resolve_wasm_file(dir_path).map_err(|e| {
+            format!(
+                "couldn't find the agent wasm file: {}, pass its path with `--wasm <FILE>`",
+                e
+            )
+        })?,
+    };
+
+    status!("wasm_file: {:?}", wasm_file_path);
+    Ok(fs::read(wasm_file_path)?)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{daemon_builder::build_daemon_parameters, manifest::ManifestParameter};
+    use super::{summary_rows, FailedChain, PublishResult, PublishedAgent};
+    use crate::{
+        daemon_builder::build_daemon_parameters, manifest::ManifestParameter, output::strip_ansi,
+    };
     use std::collections::HashMap;
 
     #[test]
@@ -200,4 +437,31 @@ mod tests {
         assert_eq!(daemon_params[0].key, "param2");
         assert_eq!(daemon_params[0].value, "user_param2_value");
     }
+
+    #[test]
+    fn test_summary_rows() {
+        let result = PublishResult {
+            metadata_id: "metadata".to_string(),
+            chain_id: "validationchain".to_string(),
+            signer: "mamoru1signer".to_string(),
+            wasm_hash: "hash".to_string(),
+            content_hash: "content_hash".to_string(),
+            agents: vec![PublishedAgent {
+                chain_name: "SUI_MAINNET".to_string(),
+                daemon_id: "daemon1".to_string(),
+                ..Default::default()
+            }],
+            failed: vec![FailedChain {
+                chain_name: "APTOS_MAINNET".to_string(),
+                error: "timeout".to_string(),
+            }],
+        };
+
+        let rows = summary_rows(&result);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], ("SUI_MAINNET".to_string(), "daemon1".to_string()));
+        assert_eq!(rows[1].0, "APTOS_MAINNET");
+        assert_eq!(strip_ansi(&rows[1].1), "FAILED: timeout");
+    }
 }
@@ -0,0 +1,342 @@
+use inline_colorization::{color_green, color_red, color_reset, color_yellow};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    agent_host::{AgentEvent, AgentHost, ReportedIncident},
+    commands::agent::publish::read_wasm_file,
+    daemon_builder::build_daemon_parameters,
+    errors::CliError,
+    manifest::{read_manifest_file, Manifest},
+    output::{Render, Table},
+    status,
+    wasm_validation::validate_agent_wasm,
+};
+
+/// Recorded chain data an agent is run against, read from a JSON file.
+#[derive(Debug, Deserialize)]
+pub struct Fixture {
+    /// Chain the data was recorded on, defaults to the first chain of the manifest.
+    pub chain: Option<String>,
+    /// Daemon parameters, manifest defaults are used for the missing ones.
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    pub events: Vec<FixtureEvent>,
+    /// Incidents the agent must report, in order. Nothing is asserted when unset.
+    pub expect: Option<Vec<ExpectedIncident>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum FixtureEvent {
+    Block(serde_json::Value),
+    Transaction(serde_json::Value),
+}
+
+/// Expected incident, unset fields match anything and `message` matches a substring.
+#[derive(Debug, Default, Deserialize)]
+pub struct ExpectedIncident {
+    pub severity: Option<String>,
+    pub message: Option<String>,
+    pub address: Option<String>,
+}
+
+/// Outcome of one fixture.
+#[derive(Debug, Default, Serialize)]
+pub struct FixtureResult {
+    pub name: String,
+    pub passed: bool,
+    pub incidents: Vec<ReportedIncident>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of `agent test`.
+#[derive(Debug, Default, Serialize)]
+pub struct TestSummary {
+    pub passed: usize,
+    pub failed: usize,
+    pub fixtures: Vec<FixtureResult>,
+}
+
+impl Render for TestSummary {
+    fn table(&self) -> Table {
+        self.fixtures.iter().fold(
+            Table::new(vec!["FIXTURE", "RESULT", "INCIDENTS"]),
+            |table, fixture| {
+                let result = match &fixture.error {
+                    None => format!("{color_green}ok{color_reset}"),
+                    Some(e) => format!("{color_red}FAILED{color_reset}: {}", e),
+                };
+                table.row(vec![
+                    fixture.name.clone(),
+                    result,
+                    fixture.incidents.len().to_string(),
+                ])
+            },
+        )
+    }
+}
+
+/// Runs the agent in `dir_path` against every `*.json` fixture in `fixtures_dir`.
+///
+/// `parameters` override the parameters of every fixture. Fixtures fail when the agent traps
+/// or the reported incidents don't match `expect`.
+pub fn test_agent(
+    dir_path: &Path,
+    fixtures_dir: &Path,
+    wasm_file: Option<&Path>,
+    parameters: &HashMap<String, String>,
+) -> Result<TestSummary, Box<dyn std::error::Error>> {
+    let manifest = read_manifest_file(dir_path)
+        .ok_or_else(|| CliError::Manifest(format!("not found in {}", dir_path.display())))?;
+    let fixture_files = fixture_files(fixtures_dir)
+        .map_err(|e| CliError::Config(format!("{}: {}", fixtures_dir.display(), e)))?;
+    if fixture_files.is_empty() {
+        return Err(
+            CliError::Config(format!("no fixtures found in {}", fixtures_dir.display())).into(),
+        );
+    }
+
+    let wasm = read_wasm_file(dir_path, wasm_file).map_err(|e| CliError::Wasm(e.to_string()))?;
+    validate_agent_wasm(&wasm).map_err(|e| CliError::Wasm(e.to_string()))?;
+    let host = AgentHost::new(&wasm).map_err(|e| CliError::Wasm(e.to_string()))?;
+
+    let mut summary = TestSummary::default();
+    for fixture_file in fixture_files {
+        let name = fixture_file
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        status!("Fixture: {color_green}{}{color_reset}", name);
+
+        let mut result = FixtureResult {
+            name,
+            ..Default::default()
+        };
+        match run_fixture(&host, &manifest, &fixture_file, parameters, &mut result) {
+            Ok(()) => {
+                status!("  {color_green}ok{color_reset}");
+                result.passed = true;
+                summary.passed += 1;
+            }
+            Err(e) => {
+                status!("  {color_red}FAILED{color_reset}: {}", e);
+                result.error = Some(e.to_string());
+                summary.failed += 1;
+            }
+        }
+        summary.fixtures.push(result);
+    }
+
+    status!(
+        "Test result: {} passed, {} failed",
+        summary.passed,
+        summary.failed
+    );
+
+    Ok(summary)
+}
+
+/// Runs one fixture, the reported incidents are kept in `result`.
+fn run_fixture(
+    host: &AgentHost,
+    manifest: &Manifest,
+    fixture_file: &Path,
+    overrides: &HashMap<String, String>,
+    result: &mut FixtureResult,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fixture: Fixture = serde_json::from_slice(&fs::read(fixture_file)?)
+        .map_err(|e| format!("invalid fixture: {}", e))?;
+
+    result.incidents = host.run(
+        fixture_parameters(manifest, &fixture, overrides),
+        &fixture_events(&fixture),
+    )?;
+    for incident in &result.incidents {
+        status!(
+            "  {color_yellow}{}{color_reset} {} {}",
+            incident.severity,
+            incident.address,
+            incident.message
+        );
+    }
+
+    match &fixture.expect {
+        Some(expected) => check_incidents(expected, &result.incidents).map_err(|e| e.into()),
+        None => Ok(()),
+    }
+}
+
+/// Returns the fixture files of `fixtures_dir`, sorted by name.
+fn fixture_files(fixtures_dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut files = fs::read_dir(fixtures_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect::<Vec<_>>();
+    files.sort();
+
+    Ok(files)
+}
+
+/// Resolves the daemon parameters the same way `publish` does.
+fn fixture_parameters(
+    manifest: &Manifest,
+    fixture: &Fixture,
+    overrides: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let chain_name = fixture
+        .chain
+        .clone()
+        .or_else(|| manifest.supported_chains.first().cloned())
+        .unwrap_or_default();
+
+    let mut user_params = fixture.parameters.clone();
+    user_params.extend(overrides.clone());
+
+    build_daemon_parameters(manifest.parameters.clone(), user_params, chain_name)
+        .into_iter()
+        .map(|parameter| (parameter.key, parameter.value))
+        .collect()
+}
+
+fn fixture_events(fixture: &Fixture) -> Vec<AgentEvent> {
+    fixture
+        .events
+        .iter()
+        .map(|event| match event {
+            FixtureEvent::Block(block) => AgentEvent::Block(block.to_string()),
+            FixtureEvent::Transaction(tx) => AgentEvent::Transaction(tx.to_string()),
+        })
+        .collect()
+}
+
+fn check_incidents(
+    expected: &[ExpectedIncident],
+    incidents: &[ReportedIncident],
+) -> Result<(), String> {
+    if expected.len() != incidents.len() {
+        return Err(format!(
+            "expected {} incidents, agent reported {}",
+            expected.len(),
+            incidents.len()
+        ));
+    }
+
+    for (index, (expected, incident)) in expected.iter().zip(incidents).enumerate() {
+        if !incident_matches(expected, incident) {
+            return Err(format!(
+                "incident {} doesn't match, expected {:?}, got {} {} {}",
+                index, expected, incident.severity, incident.address, incident.message
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn incident_matches(expected: &ExpectedIncident, incident: &ReportedIncident) -> bool {
+    expected.severity.as_ref().map_or(true, |severity| {
+        severity.eq_ignore_ascii_case(&incident.severity)
+    }) && expected
+        .message
+        .as_ref()
+        .map_or(true, |message| incident.message.contains(message.as_str()))
+        && expected.address.as_ref().map_or(true, |address| {
+            address.eq_ignore_ascii_case(&incident.address)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent_host::tests::TEST_AGENT;
+    use sealed_test::prelude::*;
+
+    const FIXTURE: &str = r#"{
+        "parameters": {"threshold": "100"},
+        "events": [
+            {"type": "block", "data": {"height": 1}},
+            {"type": "transaction", "data": {"hash": "0x01", "value": 500}}
+        ],
+        "expect": [{"severity": "alert", "message": "transfer"}]
+    }"#;
+
+    fn incident(severity: &str, message: &str) -> ReportedIncident {
+        ReportedIncident {
+            severity: severity.to_string(),
+            message: message.to_string(),
+            address: "0xabc".to_string(),
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn test_fixture_events() {
+        let fixture: Fixture = serde_json::from_str(FIXTURE).unwrap();
+        let events = fixture_events(&fixture);
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], AgentEvent::Block(block) if block == r#"{"height":1}"#));
+        assert!(matches!(&events[1], AgentEvent::Transaction(tx) if tx.contains("0x01")));
+    }
+
+    #[test]
+    fn test_check_incidents() {
+        let fixture: Fixture = serde_json::from_str(FIXTURE).unwrap();
+        let expected = fixture.expect.unwrap();
+
+        assert!(check_incidents(&expected, &[incident("alert", "Large transfer")]).is_ok());
+        assert!(check_incidents(&expected, &[incident("info", "Large transfer")]).is_err());
+        assert!(check_incidents(&expected, &[]).is_err());
+        assert!(check_incidents(&[ExpectedIncident::default()], &[incident("info", "")]).is_ok());
+    }
+
+    #[sealed_test]
+    fn test_test_agent() {
+        let dir_path = Path::new("agent");
+        fs::create_dir_all(dir_path.join("fixtures")).unwrap();
+        fs::write(
+            dir_path.join("manifest.yaml"),
+            "name: agent\nversion: {}\nsubscribable: false\ndescription: test\nlogoUrl: ''\ntags: []\nchains:\n  - SUI_MAINNET\n",
+        )
+        .unwrap();
+        let wasm_file = dir_path.join("agent.wasm");
+        fs::write(&wasm_file, wat::parse_str(TEST_AGENT).unwrap()).unwrap();
+        let transaction = r#"{"type": "transaction", "data": {"hash": "0x01"}}"#;
+        fs::write(
+            dir_path.join("fixtures/alert.json"),
+            format!(
+                r#"{{"events": [{}], "expect": [{{"severity": "alert", "message": "transfer"}}]}}"#,
+                transaction
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir_path.join("fixtures/quiet.json"),
+            format!(r#"{{"events": [{}], "expect": []}}"#, transaction),
+        )
+        .unwrap();
+
+        let summary = test_agent(
+            dir_path,
+            &dir_path.join("fixtures"),
+            Some(&wasm_file),
+            &HashMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!((summary.passed, summary.failed), (1, 1));
+        assert_eq!(summary.fixtures[0].name, "alert");
+        assert_eq!(summary.fixtures[0].incidents.len(), 1);
+        assert!(summary.fixtures[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("expected 0 incidents"));
+    }
+}
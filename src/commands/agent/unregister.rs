@@ -1,6 +1,20 @@
-use url::Url;
+use serde::Serialize;
 
-use crate::client::message_client;
+use crate::client::{grpc_url, message_client};
+use crate::output::{Render, Table};
+use crate::status;
+
+/// Agent removed by `agent unregister`.
+#[derive(Debug, Serialize)]
+pub struct UnregisterResult {
+    pub agent_id: String,
+}
+
+impl Render for UnregisterResult {
+    fn table(&self) -> Table {
+        Table::new(vec!["UNREGISTERED AGENT ID"]).row(vec![self.agent_id.clone()])
+    }
+}
 
 pub async fn unregister_agent(
     prkey: String,
@@ -9,9 +23,8 @@ pub async fn unregister_agent(
     gas_limit: u64,
     daemon_id: String,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    println!("Unresisting agent...");
-    let message_client =
-        message_client(prkey, &grpc.parse::<Url>().unwrap(), gas_limit, chain_id).await;
+    status!("Unregistering agent...");
+    let message_client = message_client(prkey, &grpc_url(&grpc)?, gas_limit, chain_id).await?;
 
     match message_client.unregister_daemon(daemon_id).await {
         Ok(response) => Ok(response.daemon_id),
@@ -0,0 +1,102 @@
+use serde::Serialize;
+use std::path::Path;
+
+use crate::commands::agent::{
+    publish::{publish_agent, PublishResult},
+    unregister::unregister_agent,
+};
+use crate::deployments::Deployments;
+use crate::output::{Render, Table};
+use crate::status;
+
+/// Result of replacing a deployed agent with a new version.
+#[derive(Debug, Serialize)]
+pub struct UpgradeResult {
+    pub published: PublishResult,
+    /// Previous agent ID, set once it was unregistered.
+    pub replaced_agent_id: Option<String>,
+}
+
+impl Render for UpgradeResult {
+    fn table(&self) -> Table {
+        let mut table = self.published.table();
+        table.headers.push("REPLACED AGENT ID");
+        for row in &mut table.rows {
+            row.push(self.replaced_agent_id.clone().unwrap_or_default());
+        }
+
+        table
+    }
+}
+
+/// Upgrades an agent deployed on `chain_name`.
+///
+/// The agent directory is published to the same chain (reusing metadata when the content is
+/// unchanged) and the previous agent is unregistered once the new one is registered. The
+/// parameters recorded for the previous agent are reused, only parameters added to the manifest
+/// since then are asked for.
+#[allow(clippy::too_many_arguments)]
+pub async fn upgrade_agent(
+    grpc: String,
+    prkey: String,
+    chain_name: String,
+    previous_agent_id: String,
+    reuse_metadata: bool,
+    profile: &str,
+    dir_path: &Path,
+    wasm_file: Option<&Path>,
+    optimize: bool,
+    gas_limit: u64,
+    chain_id: String,
+) -> Result<UpgradeResult, Box<dyn std::error::Error>> {
+    status!("Upgrading agent: {}", previous_agent_id);
+
+    let previous_params = Deployments::load(dir_path)?
+        .find_agent(&previous_agent_id)
+        .map(|deployment| deployment.parameters.clone())
+        .unwrap_or_default();
+    if !previous_params.is_empty() {
+        let mut keys = previous_params.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        status!(
+            "Reusing parameters of the previous agent: {}",
+            keys.join(", ")
+        );
+    }
+
+    let published = publish_agent(
+        grpc.clone(),
+        prkey.clone(),
+        vec![chain_name],
+        false,
+        &previous_params,
+        reuse_metadata,
+        profile,
+        dir_path,
+        wasm_file,
+        optimize,
+        gas_limit,
+        chain_id.clone(),
+    )
+    .await?;
+
+    let replaced_agent_id =
+        match unregister_agent(prkey, grpc, chain_id, gas_limit, previous_agent_id.clone()).await {
+            Ok(agent_id) => {
+                status!("Previous agent unregistered: {}", agent_id);
+                Some(previous_agent_id)
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error unregister previous agent {}: {:?}",
+                    previous_agent_id, e
+                );
+                None
+            }
+        };
+
+    Ok(UpgradeResult {
+        published,
+        replaced_agent_id,
+    })
+}
@@ -0,0 +1,159 @@
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+
+use crate::auth::Claims;
+use crate::client::Organization;
+use crate::output::{Render, Table};
+
+/// Logged in identity printed by `auth status`, the tokens themselves are never printed.
+#[derive(Debug, Serialize)]
+pub struct AuthStatus {
+    pub profile: String,
+    pub subject: String,
+    pub audience: Vec<String>,
+    pub scopes: Vec<String>,
+    pub issuer: String,
+    /// RFC 3339 time the access token expires at.
+    pub expires_at: String,
+    /// Seconds until the access token expires, negative once expired.
+    pub expires_in: i64,
+    pub refresh_token: bool,
+    /// `None` when they couldn't be listed.
+    pub organizations: Option<Vec<Organization>>,
+}
+
+impl AuthStatus {
+    pub fn new(
+        profile: &str,
+        claims: &Claims,
+        refresh_token: bool,
+        organizations: Option<Vec<Organization>>,
+        now: i64,
+    ) -> Self {
+        let expires_at = Utc
+            .timestamp_opt(claims.exp as i64, 0)
+            .single()
+            .map(|expires_at| expires_at.to_rfc3339())
+            .unwrap_or_default();
+
+        AuthStatus {
+            profile: profile.to_string(),
+            subject: claims.sub.clone(),
+            audience: claims.aud.clone(),
+            scopes: claims
+                .scope
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            issuer: claims.iss.clone(),
+            expires_at,
+            expires_in: claims.exp as i64 - now,
+            refresh_token,
+            organizations,
+        }
+    }
+}
+
+impl Render for AuthStatus {
+    fn table(&self) -> Table {
+        let expiry = if self.expires_in > 0 {
+            format!(
+                "{} (in {})",
+                self.expires_at,
+                format_duration(self.expires_in)
+            )
+        } else {
+            format!("{} (expired)", self.expires_at)
+        };
+        let organizations = match &self.organizations {
+            Some(organizations) if organizations.is_empty() => "none".to_string(),
+            Some(organizations) => organizations
+                .iter()
+                .map(|organization| format!("{} ({})", organization.name, organization.id))
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => "unknown".to_string(),
+        };
+
+        Table::new(vec!["FIELD", "VALUE"])
+            .row(vec!["Profile".to_string(), self.profile.clone()])
+            .row(vec!["Subject".to_string(), self.subject.clone()])
+            .row(vec!["Audience".to_string(), self.audience.join(", ")])
+            .row(vec!["Scopes".to_string(), self.scopes.join(" ")])
+            .row(vec!["Issuer".to_string(), self.issuer.clone()])
+            .row(vec!["Expires".to_string(), expiry])
+            .row(vec![
+                "Refresh token".to_string(),
+                self.refresh_token.to_string(),
+            ])
+            .row(vec!["Organizations".to_string(), organizations])
+    }
+}
+
+/// Largest two units of `seconds`, like `2h 5m`.
+fn format_duration(seconds: i64) -> String {
+    let units = [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+    let parts = units
+        .iter()
+        .scan(seconds, |rest, (unit, size)| {
+            let count = *rest / size;
+            *rest %= size;
+            Some((count, unit))
+        })
+        .skip_while(|(count, _)| *count == 0)
+        .take(2)
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, unit)| format!("{}{}", count, unit))
+        .collect::<Vec<_>>();
+
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_status() {
+        let claims = Claims {
+            iss: "https://auth.mamoru.ai/".to_string(),
+            sub: "auth0|user".to_string(),
+            aud: vec!["https://mamoru.ai".to_string()],
+            iat: 0,
+            exp: 7_500,
+            azp: "client".to_string(),
+            scope: "openid profile".to_string(),
+        };
+        let organizations = vec![Organization {
+            id: "org-1".to_string(),
+            name: "Mamoru".to_string(),
+        }];
+
+        let status = AuthStatus::new("default", &claims, true, Some(organizations), 0);
+        assert_eq!(status.expires_at, "1970-01-01T02:05:00+00:00");
+        assert_eq!(status.scopes, vec!["openid", "profile"]);
+
+        let table = status.table();
+        assert_eq!(
+            table.rows[5][1],
+            "1970-01-01T02:05:00+00:00 (in 2h 5m)".to_string()
+        );
+        assert_eq!(table.rows[7][1], "Mamoru (org-1)".to_string());
+
+        let expired = AuthStatus::new("default", &claims, false, None, 8_000);
+        assert_eq!(expired.expires_in, -500);
+        assert!(expired.table().rows[5][1].ends_with("(expired)"));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(3_600), "1h");
+        assert_eq!(format_duration(90_061), "1d 1h");
+    }
+}
@@ -0,0 +1,905 @@
+use crate::{
+    client::{grpc_url, list_daemon_ids, query_client, register_daemon_to_organization},
+    commands::agent::{
+        publish::{publish_to_chains, read_wasm_file, PublishResult, PublishTarget},
+        unregister::unregister_agent,
+    },
+    content_hash::agent_content_hash,
+    daemon_builder::{build_daemon_parameters, check_supported_chains},
+    deployments::{record_deployments, update_deployments, Deployment, Deployments},
+    errors::CliError,
+    manifest::{read_manifest_file, ManifestParameter},
+    output::{Render, Table},
+    status,
+    wasm_optimize::optimize_wasm,
+};
+use inline_colorization::{color_green, color_red, color_reset, color_yellow};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// Workspace file (`mamoru.deploy.yaml`) describing the agents to deploy.
+///
+/// ```yaml
+/// agents:
+///   - dir: ./agents/sui-agent
+///     organization: cbcb995c-aa56-4edb-a305-57a66edf5480
+///     params:
+///       threshold: 10
+///     chains:
+///       - name: SUI_MAINNET
+///       - name: SUI_TESTNET
+///         params:
+///           threshold: 1
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DeployFile {
+    pub agents: Vec<AgentSpec>,
+    /// Directory of the deploy file, agent directories and the deploy state are relative to it.
+    /// Both are stored as absolute paths.
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AgentSpec {
+    /// Agent directory, relative to the deploy file.
+    pub dir: PathBuf,
+    pub organization: Option<String>,
+    /// Parameters shared by all chains.
+    #[serde(default)]
+    pub params: HashMap<String, Value>,
+    pub chains: Vec<ChainSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChainSpec {
+    pub name: String,
+    #[serde(default)]
+    pub params: HashMap<String, Value>,
+}
+
+impl DeployFile {
+    pub fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = std::fs::File::open(path)?;
+        let mut deploy_file: DeployFile = serde_yaml::from_reader(file)?;
+
+        let base_dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        deploy_file.base_dir = normalize_dir(base_dir);
+        for agent in &mut deploy_file.agents {
+            agent.dir = normalize_dir(&deploy_file.base_dir.join(&agent.dir));
+        }
+
+        Ok(deploy_file)
+    }
+}
+
+/// Returns the absolute path of `dir` with symlinks resolved, so the same directory written as
+/// `./agents/x` or `agents/x` compares equal. Directories that don't exist anymore are only
+/// cleaned of `.` and `..` components.
+fn normalize_dir(dir: &Path) -> PathBuf {
+    fs::canonicalize(dir).unwrap_or_else(|_| {
+        let mut normalized = PathBuf::new();
+        for component in dir.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
+            }
+        }
+        normalized
+    })
+}
+
+const DEPLOY_STATE_DIR: &str = ".mamoru";
+const DEPLOY_STATE_FILE: &str = "deploy-state.json";
+
+/// Agent directories deployed from a deploy file.
+///
+/// Stored in `.mamoru/deploy-state.json` next to the deploy file, so agents of directories
+/// removed from the file are planned for removal.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeployState {
+    /// Agent directories, relative to the deploy file when possible.
+    pub dirs: Vec<PathBuf>,
+}
+
+impl DeployState {
+    /// Loads the state of the deploy file in `base_dir`, the directories are normalized like the
+    /// agent directories of [`DeployFile::read`].
+    pub fn load(base_dir: &Path) -> Result<Self, io::Error> {
+        let state_path = base_dir.join(DEPLOY_STATE_DIR).join(DEPLOY_STATE_FILE);
+        if !state_path.exists() {
+            return Ok(DeployState::default());
+        }
+
+        let contents = fs::read_to_string(state_path)?;
+        let mut state: DeployState = serde_json::from_str(&contents)?;
+        for dir in &mut state.dirs {
+            *dir = normalize_dir(&base_dir.join(&dir));
+        }
+
+        Ok(state)
+    }
+
+    pub fn save(&self, base_dir: &Path) -> Result<(), io::Error> {
+        let state = DeployState {
+            dirs: self
+                .dirs
+                .iter()
+                .map(|dir| dir.strip_prefix(base_dir).unwrap_or(dir).to_path_buf())
+                .collect(),
+        };
+        let state_dir = base_dir.join(DEPLOY_STATE_DIR);
+        fs::create_dir_all(&state_dir)?;
+        fs::write(
+            state_dir.join(DEPLOY_STATE_FILE),
+            serde_json::to_string_pretty(&state)?,
+        )
+    }
+}
+
+/// Saves the agent directories of the deploy file, and of the planned directories that still
+/// have deployments, as the new deploy state.
+pub fn save_deploy_state(
+    deploy_file: &DeployFile,
+    plans: &[AgentPlan],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut dirs = deploy_file
+        .agents
+        .iter()
+        .map(|agent| agent.dir.clone())
+        .collect::<Vec<_>>();
+    for plan in plans {
+        if !dirs.contains(&plan.dir) && !Deployments::load(&plan.dir)?.deployments.is_empty() {
+            dirs.push(plan.dir.clone());
+        }
+    }
+
+    Ok(DeployState { dirs }.save(&deploy_file.base_dir)?)
+}
+
+impl AgentSpec {
+    /// Returns a publish target per chain, chain parameters override the shared ones.
+    pub fn targets(&self) -> Vec<PublishTarget> {
+        self.chains
+            .iter()
+            .map(|chain| {
+                let mut params: HashMap<String, String> = self
+                    .params
+                    .iter()
+                    .map(|(key, value)| (key.clone(), param_value(value)))
+                    .collect();
+                for (key, value) in &chain.params {
+                    params.insert(key.clone(), param_value(value));
+                }
+
+                PublishTarget {
+                    chain_name: chain.name.clone(),
+                    params,
+                }
+            })
+            .collect()
+    }
+}
+
+fn param_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Bool(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        Value::Null => String::new(),
+        value => serde_yaml::to_string(value)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlanAction {
+    Create(PublishTarget),
+    Upgrade {
+        target: PublishTarget,
+        previous: Deployment,
+    },
+    Unchanged(Deployment),
+    Remove(Deployment),
+}
+
+/// Planned changes of one agent directory.
+#[derive(Debug, Serialize)]
+pub struct AgentPlan {
+    pub dir: PathBuf,
+    pub organization: Option<String>,
+    pub actions: Vec<PlanAction>,
+}
+
+/// Computes the actions bringing the `deployed` agents to the `desired` state.
+///
+/// `deployed` must only contain agents that are still registered on chain. An agent is
+/// unchanged when both its content hash and parameters match, every deployment not kept
+/// by the plan is removed. Parameters are compared as launched, see [`launch_parameters`].
+pub fn plan_agent(
+    desired: &[PublishTarget],
+    content_hash: &str,
+    manifest_parameters: Option<&[ManifestParameter]>,
+    deployed: &[Deployment],
+) -> Vec<PlanAction> {
+    let mut actions = vec![];
+    for target in desired {
+        let previous = deployed
+            .iter()
+            .rev()
+            .find(|deployment| deployment.chain_name == target.chain_name);
+
+        match previous {
+            None => actions.push(PlanAction::Create(target.clone())),
+            Some(previous)
+                if previous.content_hash.as_deref() == Some(content_hash)
+                    && launch_parameters(
+                        manifest_parameters,
+                        &previous.chain_name,
+                        &previous.parameters,
+                    ) == launch_parameters(
+                        manifest_parameters,
+                        &target.chain_name,
+                        &target.params,
+                    ) =>
+            {
+                actions.push(PlanAction::Unchanged(previous.clone()))
+            }
+            Some(previous) => actions.push(PlanAction::Upgrade {
+                target: target.clone(),
+                previous: previous.clone(),
+            }),
+        }
+    }
+
+    for deployment in deployed {
+        let kept = actions.iter().any(|action| match action {
+            PlanAction::Upgrade { previous, .. } | PlanAction::Unchanged(previous) => {
+                previous.agent_id == deployment.agent_id
+            }
+            _ => false,
+        });
+        if !kept {
+            actions.push(PlanAction::Remove(deployment.clone()));
+        }
+    }
+
+    actions
+}
+
+/// Returns the parameters an agent is launched with: the manifest defaults, overridden by the
+/// user parameters, without the parameters hidden for the chain.
+fn launch_parameters(
+    manifest_parameters: Option<&[ManifestParameter]>,
+    chain_name: &str,
+    params: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    build_daemon_parameters(
+        manifest_parameters.map(|parameters| parameters.to_vec()),
+        params.clone(),
+        chain_name.to_string(),
+    )
+    .into_iter()
+    .map(|parameter| (parameter.key, parameter.value))
+    .collect()
+}
+
+/// Builds the plan of every agent in the deploy file against the on-chain state.
+///
+/// Recorded deployments only count when their agent is still registered on chain. Agents of
+/// directories deployed before but no longer in the file, see [`DeployState`], are removed.
+/// With `optimize` the content hashes are computed on the optimized wasm, as it's uploaded.
+pub async fn build_plan(
+    grpc: &str,
+    profile: &str,
+    deploy_file: &DeployFile,
+    optimize: bool,
+) -> Result<Vec<AgentPlan>, Box<dyn std::error::Error>> {
+    let query_client = query_client(grpc_url(grpc)?).await?;
+    let live_agents = list_daemon_ids(&query_client).await?;
+
+    plan_deploy_file(deploy_file, profile, &live_agents, optimize)
+}
+
+/// Builds the plan of every agent in the deploy file, `live_agents` are the agents registered on
+/// chain.
+fn plan_deploy_file(
+    deploy_file: &DeployFile,
+    profile: &str,
+    live_agents: &HashSet<String>,
+    optimize: bool,
+) -> Result<Vec<AgentPlan>, Box<dyn std::error::Error>> {
+    let mut plans = vec![];
+    for agent in &deploy_file.agents {
+        let manifest = read_manifest_file(&agent.dir)
+            .ok_or_else(|| CliError::Manifest(format!("not found in {}", agent.dir.display())))?;
+
+        let targets = agent.targets();
+        for target in &targets {
+            if !check_supported_chains(&manifest.supported_chains, &target.chain_name) {
+                return Err(CliError::Manifest(format!(
+                    "{}: unsupported chain {}, manifest support only: {:?}",
+                    agent.dir.display(),
+                    target.chain_name,
+                    manifest.supported_chains
+                ))
+                .into());
+            }
+        }
+
+        let mut wasm =
+            read_wasm_file(&agent.dir, None).map_err(|e| CliError::Wasm(e.to_string()))?;
+        if optimize {
+            wasm = optimize_wasm(&wasm).map_err(|e| CliError::Wasm(e.to_string()))?;
+        }
+        let content_hash = agent_content_hash(&agent.dir, &wasm)?;
+        let deployed = live_deployments(&agent.dir, profile, live_agents)?;
+
+        plans.push(AgentPlan {
+            dir: agent.dir.clone(),
+            organization: agent.organization.clone(),
+            actions: plan_agent(
+                &targets,
+                &content_hash,
+                manifest.parameters.as_deref(),
+                &deployed,
+            ),
+        });
+    }
+
+    let state = DeployState::load(&deploy_file.base_dir)?;
+    for dir in state.dirs {
+        if deploy_file.agents.iter().any(|agent| agent.dir == dir) {
+            continue;
+        }
+        let deployed = live_deployments(&dir, profile, live_agents)?;
+        if !deployed.is_empty() {
+            plans.push(AgentPlan {
+                dir,
+                organization: None,
+                actions: plan_agent(&[], "", None, &deployed),
+            });
+        }
+    }
+
+    Ok(plans)
+}
+
+/// Returns the deployments of `profile` in `dir_path` whose agent is registered on chain.
+fn live_deployments(
+    dir_path: &Path,
+    profile: &str,
+    live_agents: &HashSet<String>,
+) -> Result<Vec<Deployment>, io::Error> {
+    Ok(Deployments::load(dir_path)?
+        .deployments
+        .into_iter()
+        .filter(|deployment| deployment.profile == profile)
+        .filter(|deployment| live_agents.contains(&deployment.agent_id))
+        .collect())
+}
+
+/// Counts the planned creates, upgrades, removals and unchanged agents.
+pub fn plan_summary(plans: &[AgentPlan]) -> (usize, usize, usize, usize) {
+    plans
+        .iter()
+        .flat_map(|plan| &plan.actions)
+        .fold((0, 0, 0, 0), |(c, u, r, n), action| match action {
+            PlanAction::Create(_) => (c + 1, u, r, n),
+            PlanAction::Upgrade { .. } => (c, u + 1, r, n),
+            PlanAction::Remove(_) => (c, u, r + 1, n),
+            PlanAction::Unchanged(_) => (c, u, r, n + 1),
+        })
+}
+
+impl Render for [AgentPlan] {
+    fn table(&self) -> Table {
+        let mut table = Table::new(vec!["DIR", "ACTION", "CHAIN", "AGENT ID"]);
+        for plan in self {
+            let dir = plan.dir.display().to_string();
+            for action in &plan.actions {
+                let (action, chain_name, agent_id) = match action {
+                    PlanAction::Create(target) => (
+                        format!("{color_green}+ create{color_reset}"),
+                        &target.chain_name,
+                        String::new(),
+                    ),
+                    PlanAction::Upgrade { target, previous } => (
+                        format!("{color_yellow}~ upgrade{color_reset}"),
+                        &target.chain_name,
+                        format!("{} (replaced)", previous.agent_id),
+                    ),
+                    PlanAction::Unchanged(deployment) => (
+                        "  unchanged".to_string(),
+                        &deployment.chain_name,
+                        deployment.agent_id.clone(),
+                    ),
+                    PlanAction::Remove(deployment) => (
+                        format!("{color_red}- remove{color_reset}"),
+                        &deployment.chain_name,
+                        deployment.agent_id.clone(),
+                    ),
+                };
+                table = table.row(vec![dir.clone(), action, chain_name.clone(), agent_id]);
+            }
+        }
+
+        table
+    }
+}
+
+/// Prints the plan and the number of agents per action.
+pub fn print_plan(plans: &[AgentPlan]) -> Result<(), Box<dyn std::error::Error>> {
+    crate::output::print_result(plans)?;
+
+    let (create, upgrade, remove, unchanged) = plan_summary(plans);
+    status!();
+    status!(
+        "Plan: {} to create, {} to upgrade, {} to remove, {} unchanged.",
+        create,
+        upgrade,
+        remove,
+        unchanged
+    );
+
+    Ok(())
+}
+
+/// Agents published from one directory while applying a plan.
+#[derive(Debug, Serialize)]
+pub struct DeployedAgent {
+    pub dir: PathBuf,
+    #[serde(flatten)]
+    pub result: PublishResult,
+}
+
+/// Outcome of [`apply_plan`].
+#[derive(Debug, Default, Serialize)]
+pub struct DeployReport {
+    pub deployed: Vec<DeployedAgent>,
+    /// Agents unregistered because they were upgraded or removed.
+    pub unregistered: Vec<String>,
+    pub errors: Vec<String>,
+    /// Number of [`DeployReport::errors`] that are failed organization assignments.
+    #[serde(skip)]
+    pub assignment_errors: usize,
+}
+
+impl Render for DeployReport {
+    fn table(&self) -> Table {
+        let mut table = Table::new(vec!["DIR", "CHAIN", "AGENT ID"]);
+        for deployed in &self.deployed {
+            for row in deployed.result.table().rows {
+                table = table.row(vec![
+                    deployed.dir.display().to_string(),
+                    row[0].clone(),
+                    row[2].clone(),
+                ]);
+            }
+        }
+        for agent_id in &self.unregistered {
+            table = table.row(vec![
+                String::new(),
+                String::new(),
+                format!("{} (unregistered)", agent_id),
+            ]);
+        }
+        for error in &self.errors {
+            table = table.row(vec![
+                String::new(),
+                String::new(),
+                format!("{color_red}FAILED{color_reset}: {}", error),
+            ]);
+        }
+
+        table
+    }
+}
+
+/// Connection settings used to apply a plan.
+pub struct ApplyOptions<'a> {
+    pub grpc: &'a str,
+    pub prkey: &'a str,
+    pub gas_limit: u64,
+    pub chain_id: &'a str,
+    pub profile: &'a str,
+    pub reuse_metadata: bool,
+    /// Optimize the wasm before it's registered, see [`crate::wasm_optimize::optimize_wasm`].
+    pub optimize: bool,
+    /// Organization for agents that don't set one in the deploy file.
+    pub organization_id: Option<&'a str>,
+    /// GraphQL URL and access token, agents are assigned to organizations when set.
+    pub graphql: Option<(&'a str, &'a str)>,
+}
+
+/// Applies the plan, continuing with the next agent when one fails.
+///
+/// Failed actions, including failed organization assignments, are collected in
+/// [`DeployReport::errors`].
+pub async fn apply_plan(
+    plans: &[AgentPlan],
+    options: &ApplyOptions<'_>,
+) -> Result<DeployReport, Box<dyn std::error::Error>> {
+    let mut report = DeployReport::default();
+
+    for plan in plans {
+        let organization_id = plan.organization.as_deref().or(options.organization_id);
+        let targets = plan
+            .actions
+            .iter()
+            .filter_map(|action| match action {
+                PlanAction::Create(target) | PlanAction::Upgrade { target, .. } => {
+                    Some(target.clone())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !targets.is_empty() {
+            status!("Deploying {}", plan.dir.display());
+            let manifest = read_manifest_file(&plan.dir)
+                .ok_or_else(|| format!("manifest not found in {}", plan.dir.display()))?;
+            let result = match publish_to_chains(
+                options.grpc.to_string(),
+                options.prkey.to_string(),
+                &manifest,
+                targets,
+                options.reuse_metadata,
+                options.profile,
+                &plan.dir,
+                None,
+                options.optimize,
+                options.gas_limit,
+                options.chain_id.to_string(),
+            )
+            .await
+            {
+                Ok(result) => result,
+                Err(e) => {
+                    eprintln!("Error publish agent {}: {:?}", plan.dir.display(), e);
+                    report.errors.push(format!("{}: {}", plan.dir.display(), e));
+                    continue;
+                }
+            };
+            report.errors.extend(
+                result
+                    .failed
+                    .iter()
+                    .map(|failed| format!("{}: {}", failed.chain_name, failed.error)),
+            );
+
+            record_deployments(&plan.dir, options.profile, &result, None)?;
+            if let (Some((graphql_url, token)), Some(organization_id)) =
+                (options.graphql, organization_id)
+            {
+                let mut assigned = vec![];
+                for agent in &result.agents {
+                    match register_daemon_to_organization(
+                        graphql_url,
+                        token,
+                        &agent.daemon_id,
+                        organization_id,
+                    )
+                    .await
+                    {
+                        Ok(()) => assigned.push(agent.daemon_id.as_str()),
+                        Err(e) => {
+                            eprintln!("Error graphql: {:?}", e);
+                            report.errors.push(format!(
+                                "assign {} to organization {}: {}",
+                                agent.daemon_id, organization_id, e
+                            ));
+                            report.assignment_errors += 1;
+                        }
+                    }
+                }
+                // only assigned agents keep the organization in the deployment state
+                update_deployments(&plan.dir, |deployments| {
+                    for agent_id in assigned {
+                        deployments.set_organization(agent_id, organization_id);
+                    }
+                })?;
+            }
+
+            for action in &plan.actions {
+                if let PlanAction::Upgrade { target, previous } = action {
+                    let upgraded = result
+                        .agents
+                        .iter()
+                        .any(|agent| agent.chain_name == target.chain_name);
+                    if upgraded {
+                        unregister_deployment(&plan.dir, previous, options, &mut report).await;
+                    }
+                }
+            }
+
+            report.deployed.push(DeployedAgent {
+                dir: plan.dir.clone(),
+                result,
+            });
+        }
+
+        for action in &plan.actions {
+            if let PlanAction::Remove(deployment) = action {
+                unregister_deployment(&plan.dir, deployment, options, &mut report).await;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+async fn unregister_deployment(
+    dir_path: &Path,
+    deployment: &Deployment,
+    options: &ApplyOptions<'_>,
+    report: &mut DeployReport,
+) {
+    match unregister_agent(
+        options.prkey.to_string(),
+        options.grpc.to_string(),
+        options.chain_id.to_string(),
+        options.gas_limit,
+        deployment.agent_id.clone(),
+    )
+    .await
+    {
+        Ok(agent_id) => {
+            status!("Success unregister agent: {}", agent_id);
+            if let Err(e) = update_deployments(dir_path, |deployments| {
+                deployments.remove_agent(&deployment.agent_id);
+            }) {
+                eprintln!("Couldn't update deployment state: {}", e);
+            }
+            report.unregistered.push(agent_id);
+        }
+        Err(e) => {
+            eprintln!("Error unregister agent {}: {:?}", deployment.agent_id, e);
+            report
+                .errors
+                .push(format!("unregister {}: {}", deployment.agent_id, e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sealed_test::prelude::*;
+    use std::env;
+
+    fn target(chain_name: &str, params: &[(&str, &str)]) -> PublishTarget {
+        PublishTarget {
+            chain_name: chain_name.to_string(),
+            params: params
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    fn parameter(key: &str, default_value: &str) -> ManifestParameter {
+        ManifestParameter {
+            key: key.to_string(),
+            type_: "NUMBER".to_string(),
+            title: key.to_string(),
+            description: String::new(),
+            default_value: default_value.to_string(),
+            required_for: None,
+            hidden_for: None,
+            symbol: None,
+            min: None,
+            max: None,
+            min_len: None,
+            max_len: None,
+        }
+    }
+
+    fn deployment(chain_name: &str, agent_id: &str, content_hash: &str) -> Deployment {
+        Deployment {
+            profile: "default".to_string(),
+            chain_name: chain_name.to_string(),
+            metadata_id: "metadata".to_string(),
+            agent_id: agent_id.to_string(),
+            chain_id: None,
+            signer: None,
+            tx_hash: None,
+            wasm_hash: None,
+            content_hash: Some(content_hash.to_string()),
+            parameters: HashMap::new(),
+            organization_id: None,
+            timestamp: "2024-06-01T00:00:00+00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_agent_targets() {
+        let agent: AgentSpec = serde_yaml::from_str(
+            r#"
+dir: ./agent
+params:
+  threshold: 10
+  enabled: true
+chains:
+  - name: SUI_MAINNET
+  - name: SUI_TESTNET
+    params:
+      threshold: "1"
+"#,
+        )
+        .unwrap();
+
+        let targets = agent.targets();
+
+        assert_eq!(
+            targets[0],
+            target("SUI_MAINNET", &[("threshold", "10"), ("enabled", "true")])
+        );
+        assert_eq!(
+            targets[1],
+            target("SUI_TESTNET", &[("threshold", "1"), ("enabled", "true")])
+        );
+    }
+
+    #[test]
+    fn test_plan_agent() {
+        let desired = vec![
+            target("SUI_MAINNET", &[]),
+            target("APTOS_MAINNET", &[]),
+            target("ETH_MAINNET", &[]),
+        ];
+        let deployed = vec![
+            deployment("SUI_MAINNET", "agent1", "hash"),
+            deployment("APTOS_MAINNET", "agent2", "old_hash"),
+            deployment("BSC_MAINNET", "agent3", "hash"),
+        ];
+
+        let actions = plan_agent(&desired, "hash", None, &deployed);
+
+        assert_eq!(
+            actions,
+            vec![
+                PlanAction::Unchanged(deployed[0].clone()),
+                PlanAction::Upgrade {
+                    target: desired[1].clone(),
+                    previous: deployed[1].clone(),
+                },
+                PlanAction::Create(desired[2].clone()),
+                PlanAction::Remove(deployed[2].clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_agent_parameters_changed() {
+        let desired = vec![target("SUI_MAINNET", &[("threshold", "1")])];
+        let deployed = vec![deployment("SUI_MAINNET", "agent1", "hash")];
+        let manifest_parameters = vec![parameter("threshold", "10")];
+
+        let actions = plan_agent(&desired, "hash", Some(&manifest_parameters), &deployed);
+
+        assert!(matches!(actions[0], PlanAction::Upgrade { .. }));
+    }
+
+    #[test]
+    fn test_plan_agent_default_parameters() {
+        let manifest_parameters = vec![parameter("threshold", "10"), parameter("limit", "5")];
+        let mut previous = deployment("SUI_MAINNET", "agent1", "hash");
+        previous.parameters = HashMap::from([
+            ("threshold".to_string(), "10".to_string()),
+            ("limit".to_string(), "5".to_string()),
+        ]);
+        let deployed = vec![previous];
+
+        // the deploy file only sets a parameter to its default value
+        let desired = vec![target("SUI_MAINNET", &[("threshold", "10")])];
+        let actions = plan_agent(&desired, "hash", Some(&manifest_parameters), &deployed);
+        assert_eq!(actions, vec![PlanAction::Unchanged(deployed[0].clone())]);
+
+        let desired = vec![target("SUI_MAINNET", &[("limit", "6")])];
+        let actions = plan_agent(&desired, "hash", Some(&manifest_parameters), &deployed);
+        assert!(matches!(actions[0], PlanAction::Upgrade { .. }));
+    }
+
+    #[test]
+    fn test_plan_removed_agent() {
+        let deployed = vec![
+            deployment("SUI_MAINNET", "agent1", "hash"),
+            deployment("APTOS_MAINNET", "agent2", "hash"),
+        ];
+
+        let actions = plan_agent(&[], "", None, &deployed);
+
+        assert_eq!(
+            actions,
+            vec![
+                PlanAction::Remove(deployed[0].clone()),
+                PlanAction::Remove(deployed[1].clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deploy_state() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        assert_eq!(
+            DeployState::load(tmp_dir.path()).unwrap(),
+            DeployState::default()
+        );
+
+        let state = DeployState {
+            dirs: vec![
+                tmp_dir.path().join("agents/sui-agent"),
+                PathBuf::from("/opt/agents/evm-agent"),
+            ],
+        };
+        state.save(tmp_dir.path()).unwrap();
+
+        let contents =
+            fs::read_to_string(tmp_dir.path().join(".mamoru/deploy-state.json")).unwrap();
+        assert!(contents.contains("\"agents/sui-agent\""));
+        assert_eq!(DeployState::load(tmp_dir.path()).unwrap(), state);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[sealed_test]
+    fn test_plan_deploy_file_twice() {
+        env::remove_var("CARGO_TARGET_DIR");
+        let agent_dir = Path::new("agents/sui-agent");
+        fs::create_dir_all(agent_dir.join("src")).unwrap();
+        fs::write(
+            agent_dir.join("Cargo.toml"),
+            "[package]\nname = \"sui-agent\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\ncrate-type = [\"cdylib\"]\n",
+        )
+        .unwrap();
+        fs::write(agent_dir.join("src/lib.rs"), "").unwrap();
+        fs::write(
+            agent_dir.join("manifest.yaml"),
+            "name: sui-agent\nversion: {}\nsubscribable: false\ndescription: test\nlogoUrl: ''\ntags: []\nchains:\n  - SUI_MAINNET\n",
+        )
+        .unwrap();
+        let wasm_dir = agent_dir.join("target/wasm32-wasip2/release");
+        fs::create_dir_all(&wasm_dir).unwrap();
+        fs::write(wasm_dir.join("sui_agent.wasm"), b"\0asm").unwrap();
+
+        let content_hash = agent_content_hash(agent_dir, b"\0asm").unwrap();
+        Deployments {
+            deployments: vec![deployment("SUI_MAINNET", "agent1", &content_hash)],
+        }
+        .save(agent_dir)
+        .unwrap();
+        fs::write(
+            "mamoru.deploy.yaml",
+            "agents:\n  - dir: ./agents/sui-agent\n    chains:\n      - name: SUI_MAINNET\n",
+        )
+        .unwrap();
+        let live_agents = HashSet::from(["agent1".to_string()]);
+
+        for _ in 0..2 {
+            let deploy_file = DeployFile::read(Path::new("mamoru.deploy.yaml")).unwrap();
+            let plans = plan_deploy_file(&deploy_file, "default", &live_agents, false).unwrap();
+
+            assert_eq!(plans.len(), 1);
+            assert_eq!(plan_summary(&plans), (0, 0, 0, 1));
+            save_deploy_state(&deploy_file, &plans).unwrap();
+        }
+
+        let contents = fs::read_to_string(".mamoru/deploy-state.json").unwrap();
+        assert!(contents.contains("\"agents/sui-agent\""));
+    }
+}
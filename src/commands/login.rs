@@ -1,11 +1,14 @@
-use crate::auth::TokenResponse;
-use crate::{config::Config, CommandContext};
+use crate::auth::{
+    pkce::{LoopbackListener, Pkce},
+    TokenResponse,
+};
+use crate::output::{Progress, Render, Table};
+use crate::{config::Config, status, CommandContext};
 use inline_colorization::{color_green, color_reset, color_yellow};
 
 use cred_store::CredStore;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use spinners::{Spinner, Spinners};
 use std::time::{Duration, Instant};
 use tracing::debug;
 
@@ -19,6 +22,41 @@ pub struct DeviceAuthResponse {
     interval: usize,
 }
 
+/// Token received by `login`, the tokens themselves are never printed.
+#[derive(Debug, Serialize)]
+pub struct LoginResult {
+    pub profile: String,
+    pub token_type: Option<String>,
+    pub scope: Option<String>,
+    pub expires_in: Option<usize>,
+    pub refresh_token: bool,
+}
+
+impl LoginResult {
+    pub fn new(profile: &str, token: &TokenResponse) -> Self {
+        LoginResult {
+            profile: profile.to_string(),
+            token_type: token.token_type.clone(),
+            scope: token.scope.clone(),
+            expires_in: token.expires_in,
+            refresh_token: token.refresh_token.is_some(),
+        }
+    }
+}
+
+impl Render for LoginResult {
+    fn table(&self) -> Table {
+        Table::new(vec!["PROFILE", "SCOPE", "EXPIRES IN", "REFRESH TOKEN"]).row(vec![
+            self.profile.clone(),
+            self.scope.clone().unwrap_or_default(),
+            self.expires_in
+                .map(|expires_in| format!("{}s", expires_in))
+                .unwrap_or_default(),
+            self.refresh_token.to_string(),
+        ])
+    }
+}
+
 pub async fn login(config: &Config) -> Result<TokenResponse, Box<dyn std::error::Error>> {
     let client = Client::new();
     debug!("requesting device code {}", config.mamoru_cli_auth0_domain);
@@ -41,12 +79,13 @@ pub async fn login(config: &Config) -> Result<TokenResponse, Box<dyn std::error:
         Err(e) => return Err(e.into()),
     };
 
-    println!(
+    status!(
         "Go to {color_green}{}{color_reset} and enter the code: {color_yellow}{}{color_reset}",
-        device_auth_response.verification_uri, device_auth_response.user_code
+        device_auth_response.verification_uri,
+        device_auth_response.user_code
     );
 
-    let mut sp = Spinner::new(Spinners::Triangle, "Polling for token".into());
+    let mut sp = Progress::start("Polling for token");
 
     _ = open::that(device_auth_response.verification_uri_complete);
 
@@ -64,7 +103,7 @@ pub async fn login(config: &Config) -> Result<TokenResponse, Box<dyn std::error:
             )));
         }
 
-        let resp_result = client
+        let resp_result = match client
             .post(&token_endpoint)
             .form(&[
                 ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
@@ -73,7 +112,13 @@ pub async fn login(config: &Config) -> Result<TokenResponse, Box<dyn std::error:
             ])
             .send()
             .await
-            .unwrap();
+        {
+            Ok(resp_result) => resp_result,
+            Err(e) => {
+                sp.stop();
+                return Err(e.into());
+            }
+        };
         let resp_json = resp_result.json::<TokenResponse>().await;
 
         match resp_json {
@@ -95,15 +140,100 @@ pub async fn login(config: &Config) -> Result<TokenResponse, Box<dyn std::error:
     }
 }
 
-pub fn save_tokens<T: CredStore>(
-    access_token: &str,
-    refresh_token: &str,
-    context: &mut CommandContext<T>,
+/// Time the user has to finish the browser login.
+const BROWSER_LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Logs in with the authorization code flow with PKCE, the browser is redirected to a listener on
+/// 127.0.0.1:`port`. Uses the device flow when no browser can be opened.
+pub async fn login_browser(
+    config: &Config,
+    port: u16,
+) -> Result<TokenResponse, Box<dyn std::error::Error>> {
+    let listener = LoopbackListener::bind(port).await?;
+    let redirect_uri = listener.redirect_uri()?;
+    let pkce = Pkce::generate();
+
+    let authorize_url = url::Url::parse_with_params(
+        &format!("{}/authorize", config.mamoru_cli_auth0_domain),
+        &[
+            ("response_type", "code"),
+            ("client_id", config.mamoru_cli_auth0_client_id.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("audience", config.mamoru_cli_auth0_audience.as_str()),
+            ("scope", "openid profile email offline_access"),
+            ("code_challenge", pkce.challenge.as_str()),
+            ("code_challenge_method", "S256"),
+            ("state", pkce.state.as_str()),
+        ],
+    )?;
+
+    if let Err(e) = open::that(authorize_url.as_str()) {
+        debug!("couldn't open a browser: {}", e);
+        status!(
+            "{color_yellow}Couldn't open a browser{color_reset}, using the device code instead"
+        );
+        return login(config).await;
+    }
+    status!(
+        "Continue the login in your browser, or open {color_green}{}{color_reset}",
+        authorize_url
+    );
+
+    let mut sp = Progress::start("Waiting for the browser");
+    let params = tokio::time::timeout(BROWSER_LOGIN_TIMEOUT, listener.receive()).await;
+    sp.stop();
+    let params = params.map_err(|_| {
+        std::io::Error::new(std::io::ErrorKind::TimedOut, "Browser login timed out")
+    })??;
+
+    if let Some(error) = params.get("error") {
+        let description = params.get("error_description").unwrap_or(error);
+        return Err(format!("Login failed: {}", description).into());
+    }
+    if params.get("state") != Some(&pkce.state) {
+        return Err("Login failed: state mismatch".into());
+    }
+    let code = params
+        .get("code")
+        .ok_or("Login failed: no authorization code")?;
+
+    let response = Client::new()
+        .post(format!("{}/oauth/token", config.mamoru_cli_auth0_domain))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("client_id", config.mamoru_cli_auth0_client_id.as_str()),
+            ("code_verifier", pkce.verifier.as_str()),
+            ("code", code.as_str()),
+            ("redirect_uri", redirect_uri.as_str()),
+        ])
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!(
+            "token request failed ({}): {}",
+            status,
+            response.text().await?
+        )
+        .into());
+    }
+    Ok(response.json::<TokenResponse>().await?)
+}
+
+/// Stores the tokens obtained with `grant` under the namespace of the current profile and
+/// audience, `None` for the device flow.
+pub async fn save_tokens<T: CredStore>(
+    token: &TokenResponse,
+    grant: Option<&str>,
+    context: &CommandContext<'_, T>,
 ) -> Result<(), std::io::Error> {
+    let mut tokens = token
+        .token_set(chrono::Utc::now().timestamp())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "no access token"))?;
+    tokens.grant = grant.map(str::to_string);
     context
-        .cred_store
-        .clear()
-        .add("access_token".to_string(), access_token.to_string())
-        .add("refresh_token".to_string(), refresh_token.to_string())
-        .save()
+        .cred_store()
+        .await?
+        .set(&context.namespace(), tokens)
+        .await
 }
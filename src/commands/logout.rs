@@ -1,12 +1,187 @@
-use cred_store::CredStore;
+use cred_store::{CredStore, Namespace};
+use serde::Serialize;
 
+use crate::config::Config;
+use crate::output::{Render, Table};
 use crate::CommandContext;
 
-pub fn logout<T: CredStore>(context: &mut CommandContext<T>) {
-    if context.cred_store.delete().is_err() {
-        println!("No credentials found.");
-        return;
+/// Result of `logout` for every profile and audience logged out of.
+#[derive(Debug, Serialize)]
+pub struct LogoutResult {
+    pub profiles: Vec<ProfileLogout>,
+}
+
+/// Local cleanup and revocation at Auth0 of the tokens of a profile and audience.
+#[derive(Debug, Serialize)]
+pub struct ProfileLogout {
+    pub profile: String,
+    pub audience: String,
+    /// Whether tokens were removed from the credential store.
+    pub removed: bool,
+    pub revocation: Revocation,
+}
+
+/// Outcome of the revocation of the refresh token.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status", content = "error")]
+pub enum Revocation {
+    Revoked,
+    /// Nothing to revoke, like for client credentials tokens.
+    NoRefreshToken,
+    Failed(String),
+}
+
+impl LogoutResult {
+    /// Logouts whose refresh token couldn't be revoked.
+    pub fn failed_revocations(&self) -> Vec<&ProfileLogout> {
+        self.profiles
+            .iter()
+            .filter(|profile| matches!(profile.revocation, Revocation::Failed(_)))
+            .collect()
+    }
+}
+
+impl Render for LogoutResult {
+    fn table(&self) -> Table {
+        if self.profiles.iter().all(|profile| !profile.removed) {
+            return Table::new(vec!["LOGOUT"]).row(vec!["No credentials found.".to_string()]);
+        }
+
+        self.profiles.iter().filter(|profile| profile.removed).fold(
+            Table::new(vec!["PROFILE", "AUDIENCE", "LOCAL", "REFRESH TOKEN"]),
+            |table, profile| {
+                let revocation = match &profile.revocation {
+                    Revocation::Revoked => "revoked".to_string(),
+                    Revocation::NoRefreshToken => "none".to_string(),
+                    Revocation::Failed(e) => format!("revocation failed: {}", e),
+                };
+                table.row(vec![
+                    profile.profile.clone(),
+                    profile.audience.clone(),
+                    "removed".to_string(),
+                    revocation,
+                ])
+            },
+        )
+    }
+}
+
+/// Revokes the refresh token and removes the tokens of the current profile and audience, or of
+/// every profile with `all`.
+///
+/// The tokens are removed even when the revocation fails. Tokens of other profiles are revoked at
+/// the Auth0 domain of the current settings.
+pub async fn logout<T: CredStore>(
+    context: &CommandContext<'_, T>,
+    all: bool,
+) -> Result<LogoutResult, std::io::Error> {
+    let namespaces = if all {
+        context.cred_store().await?.load().await?.namespaces()
+    } else {
+        vec![context.namespace()]
+    };
+
+    let mut profiles = vec![];
+    for namespace in namespaces {
+        profiles.push(logout_namespace(context, namespace).await?);
     }
+    if all {
+        context.cred_store().await?.delete().await?;
+    }
+
+    Ok(LogoutResult { profiles })
+}
+
+async fn logout_namespace<T: CredStore>(
+    context: &CommandContext<'_, T>,
+    namespace: Namespace,
+) -> Result<ProfileLogout, std::io::Error> {
+    let refresh_token = context
+        .cred_store()
+        .await?
+        .get(&namespace)
+        .await?
+        .and_then(|tokens| tokens.refresh);
+    let revocation = match refresh_token {
+        Some(refresh_token) => match revoke_refresh_token(context.config, &refresh_token).await {
+            Ok(()) => Revocation::Revoked,
+            Err(e) => Revocation::Failed(e.to_string()),
+        },
+        None => Revocation::NoRefreshToken,
+    };
+
+    Ok(ProfileLogout {
+        removed: context.cred_store().await?.remove(&namespace).await?,
+        profile: namespace.profile,
+        audience: namespace.audience,
+        revocation,
+    })
+}
+
+/// Revokes `refresh_token` at the OAuth revocation endpoint of Auth0.
+async fn revoke_refresh_token(
+    config: &Config,
+    refresh_token: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/oauth/revoke", config.mamoru_cli_auth0_domain))
+        .form(&[
+            ("client_id", config.mamoru_cli_auth0_client_id.as_str()),
+            ("token", refresh_token),
+        ])
+        .send()
+        .await?;
 
-    println!("Logged out.");
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("{}: {}", status, response.text().await?).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logout_result() {
+        let result = LogoutResult {
+            profiles: vec![
+                ProfileLogout {
+                    profile: "default".to_string(),
+                    audience: "https://mamoru.ai".to_string(),
+                    removed: true,
+                    revocation: Revocation::Revoked,
+                },
+                ProfileLogout {
+                    profile: "staging".to_string(),
+                    audience: "https://mamoru.ai".to_string(),
+                    removed: true,
+                    revocation: Revocation::Failed("400 Bad Request".to_string()),
+                },
+            ],
+        };
+
+        let failed = result.failed_revocations();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].profile, "staging");
+        assert_eq!(
+            result.table().rows[1][3],
+            "revocation failed: 400 Bad Request"
+        );
+        assert_eq!(
+            serde_json::to_value(&result.profiles[1].revocation).unwrap(),
+            serde_json::json!({"status": "failed", "error": "400 Bad Request"})
+        );
+
+        let empty = LogoutResult {
+            profiles: vec![ProfileLogout {
+                profile: "default".to_string(),
+                audience: "https://mamoru.ai".to_string(),
+                removed: false,
+                revocation: Revocation::NoRefreshToken,
+            }],
+        };
+        assert_eq!(empty.table().rows[0][0], "No credentials found.");
+    }
 }
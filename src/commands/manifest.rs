@@ -0,0 +1,451 @@
+use dialoguer::{Confirm, Input, MultiSelect, Select};
+use inline_colorization::{color_green, color_reset, color_yellow};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use url::Url;
+
+use crate::{
+    client::list_chain_names,
+    completion,
+    manifest::{read_manifest_file, write_manifest_file, Manifest, ManifestParameter},
+    status,
+};
+
+/// Parameter types the wizard offers.
+pub const PARAMETER_TYPES: [&str; 3] = ["STRING", "NUMBER", "BOOLEAN"];
+
+/// How long to wait for the validation chain before asking for chain names manually.
+const LIST_CHAINS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interactively creates `manifest.yaml` in `dir_path`.
+///
+/// Supported chains are offered from the validation chain when `grpc` is set and reachable,
+/// otherwise they're typed in. The written file is read back to make sure it's a valid manifest.
+pub async fn init_manifest(
+    dir_path: &Path,
+    grpc: Option<&str>,
+    force: bool,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if dir_path.join("manifest.yaml").exists()
+        && !force
+        && !Confirm::new()
+            .with_prompt("manifest.yaml already exists, overwrite it?")
+            .default(false)
+            .interact()?
+    {
+        return Err("manifest.yaml already exists".into());
+    }
+
+    let default_name = dir_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "new-agent".to_string());
+    let name: String = Input::new()
+        .with_prompt("Agent name")
+        .default(default_name)
+        .interact_text()?;
+    let description: String = Input::new().with_prompt("Description").interact_text()?;
+    let version: String = Input::new()
+        .with_prompt("Version")
+        .default("0.1.0".to_string())
+        .interact_text()?;
+    let logo_url: String = Input::new()
+        .with_prompt("Logo URL")
+        .allow_empty(true)
+        .interact_text()?;
+    let tags: String = Input::new()
+        .with_prompt("Tags, comma separated")
+        .allow_empty(true)
+        .interact_text()?;
+
+    let supported_chains = input_chains(grpc).await?;
+    let subscribable = Confirm::new()
+        .with_prompt("Can users subscribe to the agent?")
+        .default(true)
+        .interact()?;
+
+    let mut parameters: Vec<ManifestParameter> = vec![];
+    while Confirm::new()
+        .with_prompt(if parameters.is_empty() {
+            "Add a parameter?"
+        } else {
+            "Add another parameter?"
+        })
+        .default(parameters.is_empty())
+        .interact()?
+    {
+        let parameter = input_parameter(&parameters, &supported_chains)?;
+        parameters.push(parameter);
+    }
+
+    let manifest = Manifest {
+        name,
+        version: HashMap::from([("agent".to_string(), version)]),
+        subscribable,
+        description,
+        logo_url,
+        tags: parse_list(&tags),
+        supported_chains,
+        parameters: if parameters.is_empty() {
+            None
+        } else {
+            Some(parameters)
+        },
+    };
+
+    let manifest_path = write_manifest_file(dir_path, &manifest)?;
+    match read_manifest_file(dir_path) {
+        Some(written) if written == manifest => (),
+        _ => return Err("written manifest.yaml doesn't match the entered values".into()),
+    }
+
+    status!(
+        "Manifest written: {color_green}{}{color_reset}",
+        manifest_path.display()
+    );
+    Ok(manifest_path)
+}
+
+/// Offers the chains of the validation chain, or asks for chain names when it's unreachable.
+async fn input_chains(grpc: Option<&str>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let available = match grpc.map(|grpc| grpc.parse::<Url>()) {
+        Some(Ok(grpc_url)) => {
+            match tokio::time::timeout(LIST_CHAINS_TIMEOUT, list_chain_names(grpc_url)).await {
+                Ok(Ok(chains)) if !chains.is_empty() => {
+                    completion::update_history(|history| history.remember_chains(&chains));
+                    Some(chains)
+                }
+                Ok(Ok(_)) => None,
+                Ok(Err(e)) => {
+                    status!(
+                        "{color_yellow}Warning{color_reset}: couldn't list chains: {}",
+                        e
+                    );
+                    None
+                }
+                Err(_) => {
+                    status!("{color_yellow}Warning{color_reset}: listing chains timed out");
+                    None
+                }
+            }
+        }
+        Some(Err(e)) => {
+            status!(
+                "{color_yellow}Warning{color_reset}: invalid gRPC URL: {}",
+                e
+            );
+            None
+        }
+        None => None,
+    };
+
+    match available {
+        Some(available) => loop {
+            let selected = MultiSelect::new()
+                .with_prompt("Supported chains (space to select, enter to confirm)")
+                .items(&available)
+                .interact()?;
+            if !selected.is_empty() {
+                return Ok(selected.into_iter().map(|i| available[i].clone()).collect());
+            }
+            status!("Select at least one chain");
+        },
+        None => {
+            let chains: String = Input::new()
+                .with_prompt("Supported chains, comma separated (e.g. SUI_MAINNET,SUI_TESTNET)")
+                .validate_with(|input: &String| -> Result<(), &'static str> {
+                    if parse_list(input).is_empty() {
+                        Err("at least one chain is required")
+                    } else {
+                        Ok(())
+                    }
+                })
+                .interact_text()?;
+            Ok(parse_list(&chains))
+        }
+    }
+}
+
+fn input_parameter(
+    existing: &[ManifestParameter],
+    chains: &[String],
+) -> Result<ManifestParameter, Box<dyn std::error::Error>> {
+    loop {
+        let key: String = Input::new()
+            .with_prompt("Parameter key")
+            .validate_with(|input: &String| validate_key(input, existing))
+            .interact_text()?;
+        let title: String = Input::new()
+            .with_prompt("Title")
+            .default(key.clone())
+            .interact_text()?;
+        let description: String = Input::new()
+            .with_prompt("Description")
+            .allow_empty(true)
+            .interact_text()?;
+        let type_ = PARAMETER_TYPES[Select::new()
+            .with_prompt("Type")
+            .items(&PARAMETER_TYPES)
+            .default(0)
+            .interact()?]
+        .to_string();
+
+        let mut parameter = ManifestParameter {
+            type_,
+            title,
+            key,
+            description,
+            default_value: String::new(),
+            required_for: None,
+            hidden_for: None,
+            symbol: None,
+            min: None,
+            max: None,
+            min_len: None,
+            max_len: None,
+        };
+
+        match parameter.type_.as_str() {
+            "NUMBER" => {
+                parameter.min = optional_input("Minimum value")?;
+                parameter.max = optional_input("Maximum value")?;
+                parameter.symbol = optional_input("Symbol (e.g. ETH)")?;
+            }
+            "STRING" => {
+                parameter.min_len = optional_input("Minimum length")?
+                    .map(|len| len.parse())
+                    .transpose()?;
+                parameter.max_len = optional_input("Maximum length")?
+                    .map(|len| len.parse())
+                    .transpose()?;
+            }
+            _ => (),
+        }
+
+        parameter.default_value = Input::new()
+            .with_prompt("Default value")
+            .allow_empty(parameter.type_ != "BOOLEAN")
+            .default(if parameter.type_ == "BOOLEAN" {
+                "false".to_string()
+            } else {
+                String::new()
+            })
+            .interact_text()?;
+
+        if chains.len() > 1 {
+            parameter.required_for = select_chains("Required for chains", chains)?;
+            parameter.hidden_for = select_chains("Hidden for chains", chains)?;
+        }
+
+        match validate_parameter(&parameter) {
+            Ok(()) => return Ok(parameter),
+            Err(e) => status!(
+                "{color_yellow}Invalid parameter{color_reset}: {}, try again",
+                e
+            ),
+        }
+    }
+}
+
+fn optional_input(prompt: &str) -> Result<Option<String>, dialoguer::Error> {
+    let value: String = Input::new()
+        .with_prompt(format!("{} (empty to skip)", prompt))
+        .allow_empty(true)
+        .interact_text()?;
+
+    Ok(if value.trim().is_empty() {
+        None
+    } else {
+        Some(value.trim().to_string())
+    })
+}
+
+fn select_chains(prompt: &str, chains: &[String]) -> Result<Option<Vec<String>>, dialoguer::Error> {
+    let selected = MultiSelect::new()
+        .with_prompt(format!("{} (enter to skip)", prompt))
+        .items(chains)
+        .interact()?;
+
+    Ok(if selected.is_empty() {
+        None
+    } else {
+        Some(selected.into_iter().map(|i| chains[i].clone()).collect())
+    })
+}
+
+/// Splits comma separated input, dropping empty items.
+fn parse_list(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+        .collect()
+}
+
+fn validate_key(key: &str, existing: &[ManifestParameter]) -> Result<(), String> {
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err("use letters, digits, '_' and '-' only".to_string());
+    }
+    if existing.iter().any(|parameter| parameter.key == key) {
+        return Err(format!("parameter {} already exists", key));
+    }
+
+    Ok(())
+}
+
+/// Checks the bounds of a parameter and that its default value respects them.
+fn validate_parameter(parameter: &ManifestParameter) -> Result<(), String> {
+    let default_value = parameter.default_value.as_str();
+
+    match parameter.type_.as_str() {
+        "NUMBER" => {
+            let parse = |value: &Option<String>, name: &str| {
+                value
+                    .as_deref()
+                    .map(|value| {
+                        value
+                            .parse::<f64>()
+                            .map_err(|_| format!("{} {} is not a number", name, value))
+                    })
+                    .transpose()
+            };
+            let min = parse(&parameter.min, "minimum")?;
+            let max = parse(&parameter.max, "maximum")?;
+            if let (Some(min), Some(max)) = (min, max) {
+                if min > max {
+                    return Err("minimum is greater than maximum".to_string());
+                }
+            }
+
+            if !default_value.is_empty() {
+                let value = default_value
+                    .parse::<f64>()
+                    .map_err(|_| format!("default value {} is not a number", default_value))?;
+                if min.map_or(false, |min| value < min) || max.map_or(false, |max| value > max) {
+                    return Err(format!("default value {} is out of range", default_value));
+                }
+            }
+        }
+        "STRING" => {
+            if let (Some(min_len), Some(max_len)) = (parameter.min_len, parameter.max_len) {
+                if min_len > max_len {
+                    return Err("minimum length is greater than maximum length".to_string());
+                }
+            }
+
+            let len = default_value.chars().count() as u32;
+            if !default_value.is_empty()
+                && (parameter.min_len.map_or(false, |min_len| len < min_len)
+                    || parameter.max_len.map_or(false, |max_len| len > max_len))
+            {
+                return Err(format!(
+                    "default value {} doesn't respect the length limits",
+                    default_value
+                ));
+            }
+        }
+        "BOOLEAN" => {
+            if default_value != "true" && default_value != "false" {
+                return Err("default value must be true or false".to_string());
+            }
+        }
+        type_ => return Err(format!("unknown parameter type {}", type_)),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sealed_test::prelude::*;
+
+    fn parameter(type_: &str, default_value: &str) -> ManifestParameter {
+        ManifestParameter {
+            type_: type_.to_string(),
+            title: "Threshold".to_string(),
+            key: "threshold".to_string(),
+            description: "".to_string(),
+            default_value: default_value.to_string(),
+            required_for: None,
+            hidden_for: None,
+            symbol: None,
+            min: None,
+            max: None,
+            min_len: None,
+            max_len: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list(" SUI_MAINNET, ,SUI_TESTNET,"),
+            vec!["SUI_MAINNET".to_string(), "SUI_TESTNET".to_string()]
+        );
+        assert!(parse_list("").is_empty());
+    }
+
+    #[test]
+    fn test_validate_key() {
+        let existing = vec![parameter("NUMBER", "1")];
+
+        assert!(validate_key("min_value", &existing).is_ok());
+        assert!(validate_key("threshold", &existing).is_err());
+        assert!(validate_key("min value", &existing).is_err());
+        assert!(validate_key("", &existing).is_err());
+    }
+
+    #[test]
+    fn test_validate_parameter() {
+        let mut number = parameter("NUMBER", "5");
+        number.min = Some("1".to_string());
+        number.max = Some("10".to_string());
+        assert!(validate_parameter(&number).is_ok());
+
+        number.default_value = "11".to_string();
+        assert!(validate_parameter(&number).is_err());
+        number.default_value = "abc".to_string();
+        assert!(validate_parameter(&number).is_err());
+
+        let mut string = parameter("STRING", "0xabc");
+        string.max_len = Some(3);
+        assert!(validate_parameter(&string).is_err());
+        string.default_value = "".to_string();
+        assert!(validate_parameter(&string).is_ok());
+
+        assert!(validate_parameter(&parameter("BOOLEAN", "true")).is_ok());
+        assert!(validate_parameter(&parameter("BOOLEAN", "yes")).is_err());
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let mut number = parameter("NUMBER", "5");
+        number.min = Some("1".to_string());
+        number.hidden_for = Some(vec!["SUI_TESTNET".to_string()]);
+        let manifest = Manifest {
+            name: "agent".to_string(),
+            version: HashMap::from([("agent".to_string(), "0.1.0".to_string())]),
+            subscribable: true,
+            description: "Reports large transfers".to_string(),
+            logo_url: "".to_string(),
+            tags: vec!["sui".to_string()],
+            supported_chains: vec!["SUI_MAINNET".to_string(), "SUI_TESTNET".to_string()],
+            parameters: Some(vec![number]),
+        };
+
+        write_manifest_file(tmp_dir.path(), &manifest).unwrap();
+        assert_eq!(read_manifest_file(tmp_dir.path()).unwrap(), manifest);
+
+        tmp_dir.close().unwrap();
+    }
+}
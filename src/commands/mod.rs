@@ -1,3 +1,6 @@
 pub mod agent;
+pub mod auth;
+pub mod deploy;
 pub mod login;
 pub mod logout;
+pub mod manifest;
@@ -0,0 +1,279 @@
+use clap_complete::{env::Shells, CompletionCandidate, Shell};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{Error, Write},
+    path::{Path, PathBuf},
+};
+
+use cred_store::mamoru_dir;
+
+use crate::{
+    commands::agent::publish::PublishResult, deployments::Deployments, manifest::Manifest,
+};
+
+/// Environment variable the shell sets when it asks the binary for completions.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+const HISTORY_FILE: &str = "completion_history.json";
+/// Number of chain names and agent IDs kept in the history.
+const HISTORY_LIMIT: usize = 50;
+
+/// Agent printed by `publish`, `launch` or `upgrade`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentAgent {
+    pub agent_id: String,
+    pub chain_name: String,
+}
+
+/// Chain names and agent IDs offered by shell completion, most recent first.
+///
+/// Stored in `completion_history.json` of the Mamoru directory, `~/.mamorurc` by default.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompletionHistory {
+    #[serde(default)]
+    pub chains: Vec<String>,
+    #[serde(default)]
+    pub agents: Vec<RecentAgent>,
+}
+
+impl CompletionHistory {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            return Ok(CompletionHistory::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn remember_chains<'a>(&mut self, chains: impl IntoIterator<Item = &'a String>) {
+        for chain in chains {
+            self.chains.retain(|known| known != chain);
+            self.chains.insert(0, chain.clone());
+        }
+        self.chains.truncate(HISTORY_LIMIT);
+    }
+
+    pub fn remember_agents(&mut self, result: &PublishResult) {
+        for agent in &result.agents {
+            self.agents
+                .retain(|known| known.agent_id != agent.daemon_id);
+            self.agents.insert(
+                0,
+                RecentAgent {
+                    agent_id: agent.daemon_id.clone(),
+                    chain_name: agent.chain_name.clone(),
+                },
+            );
+        }
+        self.agents.truncate(HISTORY_LIMIT);
+        self.remember_chains(result.agents.iter().map(|agent| &agent.chain_name));
+    }
+}
+
+/// Writes the script registering `bin` as the completer of `shell`.
+///
+/// The script calls `bin` with `COMPLETE` set, so values like agent IDs are completed dynamically.
+pub fn write_registration(shell: Shell, bin: &Path, buf: &mut dyn Write) -> Result<(), Error> {
+    let name = env!("CARGO_BIN_NAME");
+    let completer = Shells::builtins()
+        .completer(&shell.to_string())
+        .ok_or_else(|| Error::other(format!("unsupported shell {}", shell)))?;
+
+    completer.write_registration(COMPLETE_VAR, name, name, &bin.to_string_lossy(), buf)
+}
+
+fn history_path() -> Option<PathBuf> {
+    mamoru_dir().map(|dir| dir.join(HISTORY_FILE))
+}
+
+/// Loads the history, applies `update` and saves it back.
+///
+/// The history is only a convenience for completion, so failures are ignored.
+pub fn update_history(update: impl FnOnce(&mut CompletionHistory)) {
+    let Some(path) = history_path() else {
+        return;
+    };
+    if let Ok(mut history) = CompletionHistory::load(&path) {
+        update(&mut history);
+        _ = history.save(&path);
+    }
+}
+
+fn load_history() -> CompletionHistory {
+    history_path()
+        .and_then(|path| CompletionHistory::load(&path).ok())
+        .unwrap_or_default()
+}
+
+/// Values for `--chain-name`, completed from the history and the agent in the current directory.
+pub fn chain_names() -> Vec<CompletionCandidate> {
+    chain_name_values(&load_history(), Path::new("."))
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Values for `--agent-id`, completed from the history and the agent in the current directory.
+pub fn agent_ids() -> Vec<CompletionCandidate> {
+    agent_id_values(&load_history(), Path::new("."))
+        .into_iter()
+        .map(|(agent_id, chain_name)| {
+            CompletionCandidate::new(agent_id).help(Some(chain_name.into()))
+        })
+        .collect()
+}
+
+fn chain_name_values(history: &CompletionHistory, dir_path: &Path) -> Vec<String> {
+    let mut chains: Vec<String> = vec![];
+    // read quietly, a missing manifest isn't an error while completing
+    let manifest = fs::File::open(dir_path.join("manifest.yaml"))
+        .ok()
+        .and_then(|file| serde_yaml::from_reader::<_, Manifest>(file).ok());
+    let deployments = Deployments::load(dir_path).unwrap_or_default();
+
+    let local = manifest
+        .into_iter()
+        .flat_map(|manifest| manifest.supported_chains)
+        .chain(
+            deployments
+                .deployments
+                .into_iter()
+                .rev()
+                .map(|deployment| deployment.chain_name),
+        );
+    for chain in local.chain(history.chains.iter().cloned()) {
+        if !chains.contains(&chain) {
+            chains.push(chain);
+        }
+    }
+
+    chains
+}
+
+fn agent_id_values(history: &CompletionHistory, dir_path: &Path) -> Vec<(String, String)> {
+    let mut agents: Vec<(String, String)> = vec![];
+    let deployments = Deployments::load(dir_path).unwrap_or_default();
+
+    let local = deployments
+        .deployments
+        .into_iter()
+        .rev()
+        .map(|deployment| (deployment.agent_id, deployment.chain_name));
+    let recent = history
+        .agents
+        .iter()
+        .map(|agent| (agent.agent_id.clone(), agent.chain_name.clone()));
+    for (agent_id, chain_name) in local.chain(recent) {
+        if !agents.iter().any(|(known, _)| *known == agent_id) {
+            agents.push((agent_id, chain_name));
+        }
+    }
+
+    agents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::agent::publish::PublishedAgent, deployments::Deployment};
+    use sealed_test::prelude::*;
+
+    fn published(agents: &[(&str, &str)]) -> PublishResult {
+        PublishResult {
+            agents: agents
+                .iter()
+                .map(|(chain_name, daemon_id)| PublishedAgent {
+                    chain_name: chain_name.to_string(),
+                    daemon_id: daemon_id.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_remember_keeps_recent_first() {
+        let mut history = CompletionHistory::default();
+        history.remember_agents(&published(&[("SUI_MAINNET", "agent1")]));
+        history.remember_agents(&published(&[("APTOS_MAINNET", "agent2")]));
+        history.remember_agents(&published(&[("SUI_MAINNET", "agent1")]));
+
+        let agent_ids: Vec<&str> = history
+            .agents
+            .iter()
+            .map(|agent| agent.agent_id.as_str())
+            .collect();
+        assert_eq!(agent_ids, vec!["agent1", "agent2"]);
+        assert_eq!(history.chains, vec!["SUI_MAINNET", "APTOS_MAINNET"]);
+
+        let chains: Vec<String> = (0..HISTORY_LIMIT + 10)
+            .map(|i| format!("CHAIN_{}", i))
+            .collect();
+        history.remember_chains(&chains);
+        assert_eq!(history.chains.len(), HISTORY_LIMIT);
+        assert_eq!(history.chains[0], format!("CHAIN_{}", HISTORY_LIMIT + 9));
+    }
+
+    #[test]
+    fn test_completion_values() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let mut deployments = Deployments::default();
+        deployments.add(Deployment {
+            profile: "default".to_string(),
+            chain_name: "ETH_MAINNET".to_string(),
+            metadata_id: "metadata1".to_string(),
+            agent_id: "agent3".to_string(),
+            chain_id: None,
+            signer: None,
+            tx_hash: None,
+            wasm_hash: None,
+            content_hash: None,
+            parameters: Default::default(),
+            organization_id: None,
+            timestamp: "2024-01-01T00:00:00+00:00".to_string(),
+        });
+        deployments.save(tmp_dir.path()).unwrap();
+
+        let mut history = CompletionHistory::default();
+        history.remember_agents(&published(&[
+            ("SUI_MAINNET", "agent1"),
+            ("ETH_MAINNET", "agent3"),
+        ]));
+
+        assert_eq!(
+            chain_name_values(&history, tmp_dir.path()),
+            vec!["ETH_MAINNET", "SUI_MAINNET"]
+        );
+        assert_eq!(
+            agent_id_values(&history, tmp_dir.path()),
+            vec![
+                ("agent3".to_string(), "ETH_MAINNET".to_string()),
+                ("agent1".to_string(), "SUI_MAINNET".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_history_roundtrip() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let path = tmp_dir.path().join(HISTORY_FILE);
+        assert_eq!(
+            CompletionHistory::load(&path).unwrap(),
+            CompletionHistory::default()
+        );
+
+        let mut history = CompletionHistory::default();
+        history.remember_agents(&published(&[("SUI_MAINNET", "agent1")]));
+        history.save(&path).unwrap();
+        assert_eq!(CompletionHistory::load(&path).unwrap(), history);
+    }
+}
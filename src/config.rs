@@ -13,6 +13,31 @@ pub struct Config {
     pub mamoru_graphql_url: String,
     pub mamoru_chain_id: String,
     pub mamoru_organization_id: String,
+    /// Name of the profile deployments are recorded under.
+    #[serde(default = "default_profile")]
+    pub mamoru_profile: String,
+    /// Where credentials are stored: `file`, `keychain` or `encrypted`.
+    #[serde(default = "default_cred_store")]
+    pub mamoru_cred_store: String,
+    /// Application of `login --client-credentials`.
+    #[serde(default)]
+    pub mamoru_client_id: String,
+    #[serde(default)]
+    pub mamoru_client_secret: String,
+    /// Issuer of the tokens when it isn't the Auth0 domain.
+    #[serde(default)]
+    pub mamoru_cli_auth0_issuer: Option<String>,
+    /// Scopes access tokens must have, separated by spaces.
+    #[serde(default)]
+    pub mamoru_cli_auth0_scopes: String,
+}
+
+fn default_profile() -> String {
+    "default".to_string()
+}
+
+fn default_cred_store() -> String {
+    "file".to_string()
 }
 
 impl Config {
@@ -124,6 +149,22 @@ mod tests {
             config.mamoru_organization_id, "some_organization_id",
             "organization_id should be equal"
         );
+        assert_eq!(
+            config.mamoru_profile, "default",
+            "profile should be default"
+        );
+        assert_eq!(
+            config.mamoru_cred_store, "file",
+            "credential store should be file"
+        );
+        assert!(
+            config.mamoru_client_secret.is_empty(),
+            "client secret should be empty"
+        );
+        assert!(
+            config.mamoru_cli_auth0_issuer.is_none(),
+            "issuer should default to the domain"
+        );
     }
 
     #[sealed_test]
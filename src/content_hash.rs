@@ -0,0 +1,48 @@
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Computes a hex encoded sha256 hash of the agent wasm module and its manifest.
+///
+/// Both inputs are length-prefixed so moving bytes between them changes the hash.
+pub fn content_hash(manifest: &[u8], wasm: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update((manifest.len() as u64).to_le_bytes());
+    hasher.update(manifest);
+    hasher.update((wasm.len() as u64).to_le_bytes());
+    hasher.update(wasm);
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// Computes a hex encoded sha256 hash of the wasm module.
+pub fn wasm_hash(wasm: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(wasm))
+}
+
+/// Computes the content hash of the agent in `dir_path` using its `manifest.yaml`.
+pub fn agent_content_hash(dir_path: &Path, wasm: &[u8]) -> Result<String, std::io::Error> {
+    let manifest = std::fs::read(dir_path.join("manifest.yaml"))?;
+
+    Ok(content_hash(&manifest, wasm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        let hash = content_hash(b"name: test", &[0, 1, 2, 3]);
+
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, content_hash(b"name: test", &[0, 1, 2, 3]));
+        assert_ne!(hash, content_hash(b"name: test", &[0, 1, 2, 4]));
+        assert_ne!(hash, content_hash(b"name: test2", &[0, 1, 2, 3]));
+        assert_ne!(
+            content_hash(b"ab", b"c"),
+            content_hash(b"a", b"bc"),
+            "hash must not depend only on concatenation"
+        );
+        assert_ne!(hash, wasm_hash(&[0, 1, 2, 3]));
+    }
+}
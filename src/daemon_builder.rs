@@ -7,19 +7,26 @@ use mamoru_chain_client::{
     DaemonMetadataContent, DaemonMetadataType, DaemonParameter, RegisterDaemonMetadataRequest,
 };
 
+use crate::errors::CliError;
 use crate::manifest::{self, ManifestParameter};
 
+/// Builds the metadata registration request, fails when a parameter type is unknown.
 pub fn build_daemon_metadata_request(
     manifest: &manifest::Manifest,
     wasm_content: &[u8],
-) -> RegisterDaemonMetadataRequest {
+) -> Result<RegisterDaemonMetadataRequest, CliError> {
     let mut parameters: Vec<DaemonMetadataParemeter> = vec![];
     if let Some(manifest_params) = &manifest.parameters {
         for parameter in manifest_params {
+            let type_ =
+                DaemonParemeterType::from_str_name(parameter.type_.as_str()).ok_or_else(|| {
+                    CliError::Manifest(format!(
+                        "unknown type '{}' of parameter '{}'",
+                        parameter.type_, parameter.key
+                    ))
+                })?;
             parameters.push(DaemonMetadataParemeter {
-                r#type: DaemonParemeterType::from_str_name(parameter.type_.as_str())
-                    .unwrap()
-                    .into(),
+                r#type: type_.into(),
                 title: parameter.title.clone(),
                 key: parameter.key.clone(),
                 description: parameter.description.clone(),
@@ -47,7 +54,7 @@ pub fn build_daemon_metadata_request(
         }
     }
 
-    RegisterDaemonMetadataRequest {
+    Ok(RegisterDaemonMetadataRequest {
         kind: match manifest.subscribable {
             true => DaemonMetadataType::Subcribable,
             false => DaemonMetadataType::Sole,
@@ -62,7 +69,7 @@ pub fn build_daemon_metadata_request(
         content: DaemonMetadataContent::Wasm {
             module: wasm_content.to_owned(),
         },
-    }
+    })
 }
 
 pub fn build_daemon_parameters(
@@ -113,13 +120,12 @@ mod test {
     use crate::manifest::ManifestParameter;
     use std::collections::HashMap;
 
-    #[test]
-    fn test_build_daemon_metadata_request() {
-        let manifest = crate::manifest::Manifest {
+    fn manifest(type_: &str) -> crate::manifest::Manifest {
+        crate::manifest::Manifest {
             name: "test".to_string(),
             description: "test".to_string(),
             parameters: Some(vec![ManifestParameter {
-                type_: "NUMBER".to_string(),
+                type_: type_.to_string(),
                 title: "test".to_string(),
                 key: "test".to_string(),
                 description: "test".to_string(),
@@ -137,12 +143,16 @@ mod test {
             subscribable: true,
             logo_url: "https://mamoru.ai/default-agent-logo.png".to_string(),
             version: HashMap::new(),
-        };
+        }
+    }
 
+    #[test]
+    fn test_build_daemon_metadata_request() {
+        let manifest = manifest("NUMBER");
         let wasm_content = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
 
         let request =
-            crate::daemon_builder::build_daemon_metadata_request(&manifest, &wasm_content);
+            crate::daemon_builder::build_daemon_metadata_request(&manifest, &wasm_content).unwrap();
 
         assert_eq!(request.title, "test");
         assert_eq!(request.logo_url, "https://mamoru.ai/default-agent-logo.png");
@@ -160,4 +170,16 @@ mod test {
         assert_eq!(request.parameters[0].key, "test");
         assert_eq!(request.parameters[0].title, "test");
     }
+
+    #[test]
+    fn test_build_daemon_metadata_request_unknown_type() {
+        let manifest = manifest("DECIMAL");
+
+        let error = crate::daemon_builder::build_daemon_metadata_request(&manifest, &[])
+            .err()
+            .unwrap();
+
+        assert!(matches!(error, crate::errors::CliError::Manifest(_)));
+        assert_eq!(error.exit_code(), 5);
+    }
 }
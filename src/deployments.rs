@@ -0,0 +1,362 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, io::Error, path::Path};
+
+use crate::commands::agent::publish::PublishResult;
+
+const DEPLOYMENTS_DIR: &str = ".mamoru";
+const DEPLOYMENTS_FILE: &str = "deployments.json";
+
+/// Agent deployed from an agent directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub profile: String,
+    pub chain_name: String,
+    pub metadata_id: String,
+    pub agent_id: String,
+    /// ID of the validation chain the metadata and the agent were registered on.
+    #[serde(default)]
+    pub chain_id: Option<String>,
+    /// Address of the account that registered the metadata and the agent.
+    #[serde(default)]
+    pub signer: Option<String>,
+    pub tx_hash: Option<String>,
+    pub wasm_hash: Option<String>,
+    /// Hash of the wasm module and manifest, see [`crate::content_hash::content_hash`].
+    pub content_hash: Option<String>,
+    /// User parameters the agent was launched with, when known.
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    pub organization_id: Option<String>,
+    /// RFC 3339 time of the deployment.
+    pub timestamp: String,
+}
+
+/// Deployment state stored in `.mamoru/deployments.json` of the agent directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Deployments {
+    pub deployments: Vec<Deployment>,
+}
+
+impl Deployments {
+    pub fn load(dir_path: &Path) -> Result<Self, Error> {
+        let state_path = dir_path.join(DEPLOYMENTS_DIR).join(DEPLOYMENTS_FILE);
+        if !state_path.exists() {
+            return Ok(Deployments::default());
+        }
+
+        let contents = fs::read_to_string(state_path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, dir_path: &Path) -> Result<(), Error> {
+        let state_dir = dir_path.join(DEPLOYMENTS_DIR);
+        fs::create_dir_all(&state_dir)?;
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(state_dir.join(DEPLOYMENTS_FILE), contents)
+    }
+
+    pub fn add(&mut self, deployment: Deployment) -> &mut Self {
+        self.deployments.push(deployment);
+        self
+    }
+
+    /// Forgets the deployment of an unregistered agent.
+    pub fn remove_agent(&mut self, agent_id: &str) -> &mut Self {
+        self.deployments
+            .retain(|deployment| deployment.agent_id != agent_id);
+        self
+    }
+
+    /// Stores the organization an agent was assigned to.
+    pub fn set_organization(&mut self, agent_id: &str, organization_id: &str) -> &mut Self {
+        self.deployments
+            .iter_mut()
+            .filter(|deployment| deployment.agent_id == agent_id)
+            .for_each(|deployment| deployment.organization_id = Some(organization_id.to_string()));
+        self
+    }
+
+    /// Returns the latest deployment of `agent_id`.
+    pub fn find_agent(&self, agent_id: &str) -> Option<&Deployment> {
+        self.deployments
+            .iter()
+            .rev()
+            .find(|deployment| deployment.agent_id == agent_id)
+    }
+
+    /// Returns the latest deployment of `profile`, optionally limited to `chain_name`.
+    ///
+    /// Fails when nothing was deployed or when several chains match and `chain_name` is unset.
+    pub fn find(&self, profile: &str, chain_name: Option<&str>) -> Result<&Deployment, String> {
+        let matches = self
+            .deployments
+            .iter()
+            .filter(|deployment| deployment.profile == profile)
+            .filter(|deployment| chain_name.map_or(true, |chain| deployment.chain_name == chain))
+            .collect::<Vec<_>>();
+
+        let mut chains = matches
+            .iter()
+            .map(|deployment| deployment.chain_name.as_str())
+            .collect::<Vec<_>>();
+        chains.sort();
+        chains.dedup();
+        if chains.len() > 1 {
+            return Err(format!(
+                "agent is deployed to several chains ({}), use --chain-name to pick one",
+                chains.join(", ")
+            ));
+        }
+
+        matches
+            .last()
+            .copied()
+            .ok_or_else(|| format!("no deployment recorded for profile '{}'", profile))
+    }
+
+    /// Returns the metadata ID of the latest deployment of `profile`.
+    pub fn latest_metadata_id(&self, profile: &str) -> Option<&str> {
+        self.deployments
+            .iter()
+            .rev()
+            .find(|deployment| deployment.profile == profile)
+            .map(|deployment| deployment.metadata_id.as_str())
+    }
+
+    /// Returns the metadata ID of the latest deployment of `profile` on the validation chain
+    /// `chain_id` by `signer` with the given content hash.
+    ///
+    /// Only the local deployment state is searched, metadata registered from another directory or
+    /// machine isn't found and the chain isn't queried to check that the metadata still exists.
+    pub fn find_metadata_id(
+        &self,
+        profile: &str,
+        chain_id: &str,
+        signer: &str,
+        content_hash: &str,
+    ) -> Option<&str> {
+        self.deployments
+            .iter()
+            .rev()
+            .find(|deployment| {
+                deployment.profile == profile
+                    && deployment.chain_id.as_deref() == Some(chain_id)
+                    && deployment.signer.as_deref() == Some(signer)
+                    && deployment.content_hash.as_deref() == Some(content_hash)
+            })
+            .map(|deployment| deployment.metadata_id.as_str())
+    }
+}
+
+/// Loads the deployment state of `dir_path`, applies `update` and saves it back.
+pub fn update_deployments(
+    dir_path: &Path,
+    update: impl FnOnce(&mut Deployments),
+) -> Result<(), Error> {
+    let mut deployments = Deployments::load(dir_path)?;
+    let was_empty = deployments.deployments.is_empty();
+    update(&mut deployments);
+    // don't create a state file in directories without deployments
+    if was_empty && deployments.deployments.is_empty() {
+        return Ok(());
+    }
+    deployments.save(dir_path)
+}
+
+/// Records the published agents in the deployment state of the agent directory.
+pub fn record_deployments(
+    dir_path: &Path,
+    profile: &str,
+    result: &PublishResult,
+    organization_id: Option<&str>,
+) -> Result<(), Error> {
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    update_deployments(dir_path, |deployments| {
+        for agent in &result.agents {
+            deployments.add(Deployment {
+                profile: profile.to_string(),
+                chain_name: agent.chain_name.clone(),
+                metadata_id: result.metadata_id.clone(),
+                agent_id: agent.daemon_id.clone(),
+                chain_id: Some(result.chain_id.clone()).filter(|chain_id| !chain_id.is_empty()),
+                signer: Some(result.signer.clone()).filter(|signer| !signer.is_empty()),
+                tx_hash: agent.tx_hash.clone(),
+                wasm_hash: Some(result.wasm_hash.clone()).filter(|hash| !hash.is_empty()),
+                content_hash: Some(result.content_hash.clone()).filter(|hash| !hash.is_empty()),
+                parameters: agent.params.clone(),
+                organization_id: organization_id.map(|id| id.to_string()),
+                timestamp: timestamp.clone(),
+            });
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::agent::publish::PublishedAgent;
+    use sealed_test::prelude::*;
+
+    fn deployment(profile: &str, chain_name: &str, agent_id: &str) -> Deployment {
+        Deployment {
+            profile: profile.to_string(),
+            chain_name: chain_name.to_string(),
+            metadata_id: format!("metadata-{}", agent_id),
+            agent_id: agent_id.to_string(),
+            chain_id: Some("validationchain".to_string()),
+            signer: Some("mamoru1signer".to_string()),
+            tx_hash: None,
+            wasm_hash: Some("hash".to_string()),
+            content_hash: None,
+            parameters: HashMap::new(),
+            organization_id: None,
+            timestamp: "2024-06-01T00:00:00+00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_deployment() {
+        let mut deployments = Deployments::default();
+        deployments
+            .add(deployment("default", "SUI_MAINNET", "agent1"))
+            .add(deployment("default", "SUI_MAINNET", "agent2"))
+            .add(deployment("default", "APTOS_MAINNET", "agent3"))
+            .add(deployment("prod", "SUI_MAINNET", "agent4"));
+
+        assert!(deployments.find("default", None).is_err());
+        assert_eq!(
+            deployments
+                .find("default", Some("SUI_MAINNET"))
+                .unwrap()
+                .agent_id,
+            "agent2"
+        );
+        assert_eq!(deployments.find("prod", None).unwrap().agent_id, "agent4");
+        assert!(deployments.find("staging", None).is_err());
+        assert_eq!(
+            deployments.latest_metadata_id("default"),
+            Some("metadata-agent3")
+        );
+
+        deployments.remove_agent("agent3");
+        assert_eq!(
+            deployments.find("default", None).unwrap().agent_id,
+            "agent2"
+        );
+    }
+
+    #[test]
+    fn test_find_agent() {
+        let mut deployments = Deployments::default();
+        let mut agent1 = deployment("default", "SUI_MAINNET", "agent1");
+        agent1.parameters = HashMap::from([("threshold".to_string(), "1".to_string())]);
+        deployments
+            .add(agent1)
+            .add(deployment("default", "SUI_MAINNET", "agent2"));
+
+        assert_eq!(
+            deployments.find_agent("agent1").unwrap().parameters["threshold"],
+            "1"
+        );
+        assert_eq!(
+            deployments.find_agent("agent2").unwrap().chain_name,
+            "SUI_MAINNET"
+        );
+        assert!(deployments.find_agent("agent3").is_none());
+    }
+
+    #[test]
+    fn test_find_metadata_id() {
+        let mut deployments = Deployments::default();
+        let mut agent1 = deployment("default", "SUI_MAINNET", "agent1");
+        agent1.content_hash = Some("content".to_string());
+        let mut agent2 = deployment("prod", "SUI_MAINNET", "agent2");
+        agent2.content_hash = Some("content".to_string());
+        agent2.signer = Some("mamoru1other".to_string());
+        let mut agent3 = deployment("default", "SUI_MAINNET", "agent3");
+        agent3.content_hash = Some("content".to_string());
+        agent3.chain_id = Some("testchain".to_string());
+        deployments.add(agent1).add(agent2).add(agent3);
+
+        assert_eq!(
+            deployments.find_metadata_id("default", "validationchain", "mamoru1signer", "content"),
+            Some("metadata-agent1")
+        );
+        assert_eq!(
+            deployments.find_metadata_id("prod", "validationchain", "mamoru1other", "content"),
+            Some("metadata-agent2")
+        );
+        assert_eq!(
+            deployments.find_metadata_id("default", "testchain", "mamoru1signer", "content"),
+            Some("metadata-agent3")
+        );
+        assert_eq!(
+            deployments.find_metadata_id("default", "validationchain", "mamoru1signer", "other"),
+            None
+        );
+        assert_eq!(
+            deployments.find_metadata_id("default", "validationchain", "mamoru1unknown", "content"),
+            None
+        );
+        assert_eq!(
+            deployments.find_metadata_id("prod", "validationchain", "mamoru1signer", "content"),
+            None
+        );
+        assert_eq!(
+            deployments.find_metadata_id("default", "devchain", "mamoru1signer", "content"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_deployments() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        assert!(Deployments::load(tmp_dir.path())
+            .unwrap()
+            .deployments
+            .is_empty());
+
+        let mut deployments = Deployments::default();
+        deployments.add(deployment("default", "SUI_MAINNET", "agent1"));
+        deployments.save(tmp_dir.path()).unwrap();
+
+        let loaded = Deployments::load(tmp_dir.path()).unwrap();
+        assert_eq!(loaded.deployments, deployments.deployments);
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_record_deployments() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let result = PublishResult {
+            metadata_id: "metadata".to_string(),
+            chain_id: "validationchain".to_string(),
+            signer: "mamoru1signer".to_string(),
+            wasm_hash: "wasm".to_string(),
+            content_hash: "content".to_string(),
+            agents: vec![PublishedAgent {
+                chain_name: "SUI_MAINNET".to_string(),
+                daemon_id: "agent1".to_string(),
+                params: HashMap::from([("threshold".to_string(), "1".to_string())]),
+                tx_hash: Some("ABCDEF".to_string()),
+            }],
+            ..Default::default()
+        };
+
+        record_deployments(tmp_dir.path(), "default", &result, Some("org")).unwrap();
+
+        let loaded = Deployments::load(tmp_dir.path()).unwrap();
+        let deployment = &loaded.deployments[0];
+        assert_eq!(deployment.agent_id, "agent1");
+        assert_eq!(deployment.metadata_id, "metadata");
+        assert_eq!(deployment.chain_id.as_deref(), Some("validationchain"));
+        assert_eq!(deployment.signer.as_deref(), Some("mamoru1signer"));
+        assert_eq!(deployment.tx_hash.as_deref(), Some("ABCDEF"));
+        assert_eq!(deployment.content_hash.as_deref(), Some("content"));
+        assert_eq!(deployment.parameters["threshold"], "1");
+        assert_eq!(deployment.organization_id.as_deref(), Some("org"));
+
+        tmp_dir.close().unwrap();
+    }
+}
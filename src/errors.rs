@@ -1,5 +1,94 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
+/// Failure of a command, the variant decides the exit code of the CLI.
+///
+/// | Code | Failure                                                   |
+/// |------|-----------------------------------------------------------|
+/// | 1    | [`CliError::Failed`], e.g. failed fixtures or deployments |
+/// | 2    | invalid arguments, reported by clap                       |
+/// | 3    | [`CliError::Config`]                                      |
+/// | 4    | [`CliError::Auth`]                                        |
+/// | 5    | [`CliError::Manifest`]                                    |
+/// | 6    | [`CliError::Wasm`]                                        |
+/// | 7    | [`CliError::Chain`]                                       |
+/// | 8    | [`CliError::Graphql`]                                     |
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    Failed(String),
+    /// Missing or invalid settings, arguments or local files.
+    Config(String),
+    /// Missing, expired or invalid credentials.
+    Auth(String),
+    Manifest(String),
+    /// Agent build, artifact, validation or optimization failure.
+    Wasm(String),
+    /// Query or transaction on the validation chain failed.
+    Chain(String),
+    Graphql(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Failed(_) => 1,
+            CliError::Config(_) => 3,
+            CliError::Auth(_) => 4,
+            CliError::Manifest(_) => 5,
+            CliError::Wasm(_) => 6,
+            CliError::Chain(_) => 7,
+            CliError::Graphql(_) => 8,
+        }
+    }
+
+    /// Converts `error` into a `kind` error, unless it already is a [`CliError`].
+    pub fn from_boxed(error: Box<dyn std::error::Error>, kind: fn(String) -> CliError) -> Self {
+        match error.downcast::<CliError>() {
+            Ok(error) => *error,
+            Err(error) => kind(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Failed(message) | CliError::Config(message) => write!(f, "{}", message),
+            CliError::Auth(message) => write!(f, "authentication failed: {}", message),
+            CliError::Manifest(message) => write!(f, "invalid manifest: {}", message),
+            CliError::Wasm(message) => write!(f, "invalid agent wasm: {}", message),
+            CliError::Chain(message) => write!(f, "chain request failed: {}", message),
+            CliError::Graphql(message) => write!(f, "GraphQL request failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<Box<dyn std::error::Error>> for CliError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        CliError::from_boxed(error, CliError::Failed)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(error: std::io::Error) -> Self {
+        CliError::Failed(error.to_string())
+    }
+}
+
+/// Categorizes the error of a result, see [`CliError::from_boxed`].
+pub trait OrCliError<T> {
+    fn or_cli_error(self, kind: fn(String) -> CliError) -> Result<T, CliError>;
+}
+
+impl<T, E: Into<Box<dyn std::error::Error>>> OrCliError<T> for Result<T, E> {
+    fn or_cli_error(self, kind: fn(String) -> CliError) -> Result<T, CliError> {
+        self.map_err(|error| CliError::from_boxed(error.into(), kind))
+    }
+}
+
+/// GraphQL error returned by the Mamoru API.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Error {
     pub message: String,
@@ -32,3 +121,43 @@ pub struct Exception {
 pub struct ResponseData {
     pub errors: Option<Vec<Error>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            CliError::Failed(String::new()),
+            CliError::Config(String::new()),
+            CliError::Auth(String::new()),
+            CliError::Manifest(String::new()),
+            CliError::Wasm(String::new()),
+            CliError::Chain(String::new()),
+            CliError::Graphql(String::new()),
+        ];
+
+        let mut codes = errors.iter().map(CliError::exit_code).collect::<Vec<_>>();
+        codes.sort();
+        codes.dedup();
+
+        assert_eq!(codes, vec![1, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_or_cli_error_keeps_category() {
+        let boxed: Result<(), Box<dyn std::error::Error>> =
+            Err(CliError::Wasm("missing export".to_string()).into());
+        assert_eq!(
+            boxed.or_cli_error(CliError::Chain),
+            Err(CliError::Wasm("missing export".to_string()))
+        );
+
+        let plain: Result<(), String> = Err("connection refused".to_string());
+        assert_eq!(
+            plain.or_cli_error(CliError::Chain),
+            Err(CliError::Chain("connection refused".to_string()))
+        );
+    }
+}
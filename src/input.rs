@@ -3,28 +3,39 @@ use dialoguer::{Input, Select};
 use inline_colorization::{color_reset, color_yellow};
 use std::collections::HashMap;
 
+/// Asks the user for the value of every manifest parameter visible on `chain_name` that isn't
+/// already in `user_params`.
 pub fn input_user_params(
     manifest_params: &Vec<ManifestParameter>,
+    chain_name: &str,
     user_params: &mut HashMap<String, String>,
-) {
+) -> Result<(), dialoguer::Error> {
     for param in manifest_params {
+        if let Some(hidden_for) = &param.hidden_for {
+            if hidden_for.iter().any(|chain| chain == chain_name) {
+                continue;
+            }
+        }
+        if user_params.contains_key(&param.key) {
+            continue;
+        }
         let param_name = param.key.as_str();
         let user_input: String = Input::new()
             .with_prompt(format!(
-                "Enter value for {color_yellow}{}{color_reset}",
-                param_name
+                "Enter value for {color_yellow}{}{color_reset} ({})",
+                param_name, chain_name
             ))
             .default(param.default_value.as_str().into())
-            .interact_text()
-            .unwrap();
+            .interact_text()?;
         user_params.insert(param_name.to_string(), user_input);
     }
+
+    Ok(())
 }
 
-pub fn select_user_input(items: Vec<String>) -> usize {
+pub fn select_user_input(items: Vec<String>) -> Result<usize, dialoguer::Error> {
     Select::new()
         .with_prompt("What do you choose?")
         .items(&items)
         .interact()
-        .unwrap()
 }
@@ -1,427 +1,304 @@
+mod agent_host;
+mod artifact;
 mod auth;
+mod cli;
 mod client;
 mod commands;
+mod completion;
 mod config;
+mod content_hash;
 mod daemon_builder;
+mod deployments;
 mod errors;
 mod input;
 mod manifest;
-
-use auth::{get_token::get_token, jwtverifier::JwtVerifier, Claims};
-use client::register_daemon_to_organization;
+mod output;
+mod wasm_optimize;
+mod wasm_validation;
+
+use auth::{
+    get_token::{get_token, request_client_credentials_token},
+    jwtverifier::JwtVerifier,
+    Claims, CLIENT_CREDENTIALS_GRANT,
+};
+use cli::{AgentCommands, AuthCommands, Cli, Commands, DeploymentArgs, ManifestCommands, WasmArgs};
+use client::{list_organizations, register_daemon_to_organization};
+use commands::agent::publish::{PublishResult, PublishedAgent};
 use config::Config;
-use cred_store::{CredStore, Credentials};
-
-use clap::{arg, command, value_parser, Arg, ArgMatches};
-use std::{env, fs, panic, path::PathBuf};
+use cred_store::{
+    mamoru_dir, CredStore, Credentials, EncryptedFileStore, EnvStore, Namespace, Store,
+    PASSPHRASE_ENV,
+};
+use deployments::Deployments;
+use errors::{CliError, OrCliError};
+
+use clap::{CommandFactory, Parser};
+use futures::future::LocalBoxFuture;
+use inline_colorization::{color_reset, color_yellow};
+use jsonwebtoken::Algorithm;
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, IsTerminal},
+    panic,
+    path::{Path, PathBuf},
+};
+use tokio::sync::OnceCell;
+
+/// Opens the credential store of a [`CommandContext`].
+type OpenCredStore<'a, T> = Box<dyn Fn() -> LocalBoxFuture<'a, Result<T, io::Error>> + 'a>;
 
 pub struct CommandContext<'a, T: CredStore> {
     pub config: &'a Config,
-    pub cred_store: &'a mut T,
+    cred_store: OnceCell<T>,
+    open_cred_store: OpenCredStore<'a, T>,
+}
+
+impl<'a, T: CredStore> CommandContext<'a, T> {
+    pub fn new(config: &'a Config, open_cred_store: OpenCredStore<'a, T>) -> Self {
+        CommandContext {
+            config,
+            cred_store: OnceCell::new(),
+            open_cred_store,
+        }
+    }
+
+    /// Credential store, opened on first use so commands that don't need tokens never ask for
+    /// a passphrase or read the keychain.
+    pub async fn cred_store(&self) -> Result<&T, io::Error> {
+        self.cred_store
+            .get_or_try_init(|| (self.open_cred_store)())
+            .await
+    }
+
+    /// Namespace of the tokens of the current profile and audience.
+    pub fn namespace(&self) -> Namespace {
+        Namespace::new(
+            &self.config.mamoru_profile,
+            &self.config.mamoru_cli_auth0_audience,
+        )
+    }
+
+    /// Stored access token, without refreshing it, see [`get_token`].
+    async fn access_token(&self) -> Result<String, CliError> {
+        self.cred_store()
+            .await
+            .or_cli_error(CliError::Auth)?
+            .get(&self.namespace())
+            .await
+            .or_cli_error(CliError::Auth)?
+            .map(|tokens| tokens.access)
+            .ok_or_else(|| CliError::Auth("access token not found".to_string()))
+    }
 }
 
-const MAMORU_CONFIG_DIR: &str = ".mamorurc";
 const CONFIG_NAME: &str = "settings.toml";
 const CREDENTIALS: &str = ".credentials";
+const JWKS_CACHE: &str = "jwks.json";
+/// Algorithms Auth0 signs access tokens with.
+const AUTH0_ALGORITHMS: [Algorithm; 1] = [Algorithm::RS256];
+/// Seconds of clock skew with Auth0 allowed when checking token expiry.
+const AUTH0_LEEWAY: u64 = 60;
 
+/// Exits with the code of the error category, see [`CliError`].
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let home_dir = match dirs::home_dir() {
-        Some(home) => home,
-        None => {
-            panic!("failed to get home directory");
-        }
-    };
+async fn main() {
+    // answers the completion requests of the scripts printed by `completions`
+    clap_complete::CompleteEnv::with_factory(Cli::command)
+        .var(completion::COMPLETE_VAR)
+        .complete();
+
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+async fn run() -> Result<(), CliError> {
+    let cli = Cli::parse();
+    output::init(cli.output);
 
-    let mamoru_dir_path = home_dir.join(MAMORU_CONFIG_DIR);
+    let mamoru_dir_path =
+        mamoru_dir().ok_or_else(|| CliError::Config("failed to get home directory".to_string()))?;
     if !mamoru_dir_path.exists() {
         fs::create_dir_all(mamoru_dir_path.clone())?;
     }
     let settings_file = mamoru_dir_path.join(CONFIG_NAME);
     let credentials_file = mamoru_dir_path.join(CREDENTIALS);
 
-    let config = config::Config::from_env(settings_file.to_str()).expect("failed to load config");
+    let config = config::Config::from_env(settings_file.to_str()).or_cli_error(CliError::Config)?;
 
-    let mut credentials = Credentials::new()
+    let credentials_file = Credentials::new()
         .set_file_name(
             credentials_file
                 .to_str()
-                .expect("failed to get credentials")
+                .ok_or_else(|| CliError::Config("invalid credentials path".to_string()))?
                 .to_string(),
         )
-        .build()
-        .load()
-        .expect("failed to load credentials");
-
-    let mut context = CommandContext {
-        config: &config,
-        cred_store: &mut credentials,
-    };
-
-    let matches = command!()
-        .about("mamoru cli tool")
-        .arg_required_else_help(true)
-        .subcommand(
-            command!("agent")
-                .about("Manage agents")
-                .arg_required_else_help(true)
-                .subcommand(
-                    command!("publish")
-                        .about("Publish an agent")
-                        .arg_required_else_help(true)
-                        .arg(
-                            arg!(--grpc <GRPC> "gRPC URL")
-                                .required(false)
-                                .env("MAMORU_RPC_URL"),
-                        )
-                        .arg(
-                            arg!(-k --key <KEY> "Private key")
-                                .required(false)
-                                .env("MAMORU_PRIVATE_KEY"),
-                        )
-                        .arg(arg!(-c --"chain-name" <CHAIN_NAME> "Chain name").required(true))
-                        .arg(
-                            arg!(--"gas-limit" <GAS_LIMIT> "Gas limit")
-                                .default_value("200000000")
-                                .env("MAMORU_GAS_LIMIT"),
-                        )
-                        .arg(arg!(--"chain-id" <CHAIN_ID> "Chain ID").required(false))
-                        .arg(
-                            arg!(-o --"organization-id" <ORGANIZATION_ID> "Organization ID")
-                                .required(false),
-                        )
-                        .arg(
-                            Arg::new("file")
-                                .help("Path to Agent directory")
-                                .required(true)
-                                .value_parser(value_parser!(PathBuf)),
-                        ),
-                )
-                .subcommand(
-                    command!("new")
-                        .about("Create a new agent")
-                        .arg(arg!(-n [name] "Agent name").default_value("new-agent")),
-                )
-                .subcommand(
-                    command!("launch")
-                        .about("Publish an agent to existing metadata")
-                        .arg_required_else_help(true)
-                        .arg(arg!(-m --"metadata-id" <METADATA_ID> "Metadata ID").required(true))
-                        .arg(arg!(-c --"chain-name" <CHAIN_NAME> "Chain name").required(true))
-                        .arg(
-                            arg!(--"gas-limit" <GAS_LIMIT> "Gas limit")
-                                .default_value("200000000")
-                                .env("MAMORU_GAS_LIMIT"),
-                        )
-                        .arg(
-                            arg!(-k --key <KEY> "Private key")
-                                .required(false)
-                                .env("MAMORU_PRIVATE_KEY"),
-                        )
-                        .arg(
-                            arg!(--grpc <GRPC> "gRPC URL")
-                                .required(false)
-                                .env("MAMORU_RPC_URL"),
-                        )
-                        .arg(arg!(--"chain-id" <CHAIN_ID> "Chain ID").required(false))
-                        .arg(
-                            arg!(-o --"organization-id" <ORGANIZATION_ID> "Organization ID")
-                                .required(false),
-                        )
-                        .arg(
-                            Arg::new("file")
-                                .help("Path to Agent directory")
-                                .required(true)
-                                .value_parser(value_parser!(PathBuf)),
-                        ),
-                )
-                .subcommand(
-                    command!("assign")
-                        .about("Assign an agent to an organization")
-                        .arg_required_else_help(true)
-                        .arg(arg!(-d --"agent-id" <AGENT_ID> "Agent ID").required(true))
-                        .arg(
-                            arg!(-o --"organization-id" <ORGANIZATION_ID> "Organization ID")
-                                .required(false),
-                        )
-                        .arg(arg!(--"graphql-url" <GRAPHQL_URL> "GraphQL URL").required(false)),
-                )
-                .subcommand(
-                    command!("unregister")
-                        .about("Unregister an agent")
-                        .arg_required_else_help(true)
-                        .arg(arg!(-d --"agent-id" <AGENT_ID> "Agent ID").required(true))
-                        .arg(
-                            arg!(--"gas-limit" <GAS_LIMIT> "Gas limit")
-                                .default_value("200000000")
-                                .env("MAMORU_GAS_LIMIT"),
-                        )
-                        .arg(
-                            arg!(-k --key <KEY> "Private key")
-                                .required(false)
-                                .env("MAMORU_PRIVATE_KEY"),
-                        )
-                        .arg(
-                            arg!(--grpc <GRPC> "gRPC URL")
-                                .required(false)
-                                .env("MAMORU_RPC_URL"),
-                        )
-                        .arg(arg!(--"chain-id" <CHAIN_ID> "Chain ID").required(false)),
-                ),
-        )
-        .subcommand(command!("logout").about("Logout from mamoru"))
-        .subcommand(command!("login").about("Login to mamoru"))
-        .get_matches();
-
-    if let Some(agent_matches) = matches.subcommand_matches("agent") {
-        if let Some(publish_matches) = agent_matches.subcommand_matches("publish") {
-            let grpc: String = match publish_matches.get_one::<String>("grpc") {
-                Some(grpc) => grpc.to_string(),
-                None => {
-                    if context.config.mamoru_rpc_url.is_empty() {
-                        eprintln!("gRPC URL required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_rpc_url.clone()
-                    }
-                }
-            };
-            if grpc != "http://localhost:9090" && grpc != "http://127.0.0.1:9090" {
-                println!("Checking auth");
-                check_auth(&mut context).await?;
+        .build();
+    let context = CommandContext::new(&config, {
+        let config = &config;
+        Box::new(move || Box::pin(open_cred_store(config, credentials_file.clone())))
+    });
+
+    match cli.command {
+        Commands::Agent(AgentCommands::Publish(args)) => {
+            let grpc = args.chain.grpc(context.config)?;
+            let remote = !cli::is_local_grpc(&grpc);
+            if remote {
+                status!("Checking auth");
+                check_auth(&context).await?;
             }
 
-            let file_path = publish_matches
-                .get_one::<PathBuf>("file")
-                .expect("filepath required")
-                .canonicalize()
-                .expect("invalid file path");
-
-            let prkey: String = match publish_matches.get_one::<String>("key") {
-                Some(key) => key.to_string(),
-                None => {
-                    if context.config.mamoru_private_key.is_empty() {
-                        eprintln!("Private key required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_private_key.clone()
-                    }
-                }
-            };
+            let file_path = canonical_path(&args.file)?;
+            let chain = args.chain.resolve(context.config)?;
+            let wasm_file = wasm_file(&args.wasm, &file_path)?;
+            let organization_id = args.organization.resolve(context.config)?;
+
+            let result = commands::agent::publish::publish_agent(
+                chain.grpc,
+                chain.prkey,
+                args.chain_names,
+                args.all_supported,
+                &HashMap::new(),
+                !args.new_metadata,
+                &context.config.mamoru_profile,
+                &file_path,
+                wasm_file.as_deref(),
+                args.optimize,
+                chain.gas_limit,
+                chain.chain_id,
+            )
+            .await
+            .or_cli_error(CliError::Chain)?;
 
-            let gas_limit: String = match publish_matches.get_one::<String>("gas-limit") {
-                Some(gas_limit) => gas_limit.to_string(),
-                None => {
-                    if context.config.mamoru_gas_limit.is_empty() {
-                        eprintln!("Gas limit required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_gas_limit.clone()
-                    }
-                }
+            let assigned = if remote {
+                assign_agents(&context, &result.agents, &organization_id).await
+            } else {
+                Ok(())
             };
+            record_deployments(
+                &file_path,
+                context.config,
+                &result,
+                remote.then_some(organization_id.as_str()),
+            );
+            output::print_result(&result)?;
+            check_failed_chains(&result)?;
+            assigned?;
+        }
 
-            let chain_name = publish_matches
-                .get_one::<String>("chain-name")
-                .expect("chain-name required")
-                .to_string();
-
-            let gas_limit = gas_limit
-                .parse::<u64>()
-                .expect("gas limit must be a number");
+        Commands::Agent(AgentCommands::Build { file, target }) => {
+            let file_path = canonical_path(&file)?;
 
-            let chain_id: String = match publish_matches.get_one::<String>("chain-id") {
-                Some(chain_id) => chain_id.to_string(),
-                None => {
-                    if context.config.mamoru_chain_id.is_empty() {
-                        eprintln!("Chain ID required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_chain_id.clone()
-                    }
-                }
-            };
+            commands::agent::build::build_agent(&file_path, target.as_deref())
+                .or_cli_error(CliError::Wasm)?;
+        }
 
-            let organization_id = get_organization_id(publish_matches, &context);
+        Commands::Agent(AgentCommands::Test(args)) => {
+            let file_path = canonical_path(&args.file)?;
+            let fixtures_dir = args.fixtures.unwrap_or_else(|| file_path.join("fixtures"));
+            let parameters: HashMap<String, String> = args.params.into_iter().collect();
+            let wasm_file = wasm_file(&args.wasm, &file_path)?;
 
-            let publish_result = commands::agent::publish::publish_agent(
-                grpc.clone(),
-                prkey,
-                chain_name,
+            let summary = commands::agent::test::test_agent(
                 &file_path,
-                gas_limit,
-                chain_id,
-            )
-            .await;
-
-            if grpc != "http://localhost:9090" && grpc != "http://127.0.0.1:9090" {
-                println!("Assign agent to organization");
-                let token = context
-                    .cred_store
-                    .get("access_token")
-                    .expect("access_token required");
-                match publish_result {
-                    Ok(daemon_id) => {
-                        match register_daemon_to_organization(
-                            context.config.mamoru_graphql_url.as_str(),
-                            token,
-                            daemon_id.as_str(),
-                            organization_id.as_str(),
-                        )
-                        .await
-                        {
-                            Ok(_) => (),
-                            Err(e) => println!("Error graphql: {:?}", e),
-                        }
-                    }
-                    Err(e) => println!("Error publish agent: {:?}", e),
-                }
+                &fixtures_dir,
+                wasm_file.as_deref(),
+                &parameters,
+            )?;
+            output::print_result(&summary)?;
+            if summary.failed > 0 {
+                return Err(CliError::Failed(format!(
+                    "{} fixture(s) failed",
+                    summary.failed
+                )));
             }
         }
 
-        if let Some(new_matches) = agent_matches.subcommand_matches("new") {
-            #[allow(unused_variables)]
-            let name = new_matches.get_one::<String>("name").unwrap().to_string();
-            #[cfg(feature = "no-ssl")]
-            commands::agent::new::create_new_agent(name);
-
-            #[cfg(not(feature = "no-ssl"))]
-            println!("Feature 'no-ssl' enabled");
+        Commands::Agent(AgentCommands::New(args)) => {
+            if args.list_templates {
+                output::print_result(commands::agent::new::templates().as_slice())?;
+            } else {
+                commands::agent::new::create_new_agent(
+                    &args.name,
+                    args.template.as_deref(),
+                    args.description,
+                    args.chain_names,
+                )?;
+            }
         }
 
-        if let Some(launch_matches) = agent_matches.subcommand_matches("launch") {
-            let grpc: String = match launch_matches.get_one::<String>("grpc") {
-                Some(grpc) => grpc.to_string(),
-                None => {
-                    if context.config.mamoru_rpc_url.is_empty() {
-                        eprintln!("gRPC URL required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_rpc_url.clone()
-                    }
-                }
-            };
-            if grpc != "http://localhost:9090" && grpc != "http://127.0.0.1:9090" {
-                println!("Checking auth");
-                check_auth(&mut context).await?;
+        Commands::Agent(AgentCommands::Launch(args)) => {
+            let grpc = args.chain.grpc(context.config)?;
+            let remote = !cli::is_local_grpc(&grpc);
+            if remote {
+                status!("Checking auth");
+                check_auth(&context).await?;
             }
 
-            let file_path = launch_matches
-                .get_one::<PathBuf>("file")
-                .expect("filepath required")
-                .canonicalize()
-                .expect("invalid file path");
-            let metadata_id = launch_matches
-                .get_one::<String>("metadata-id")
-                .expect("metadata-id required")
-                .to_string();
-            let chain_name = launch_matches
-                .get_one::<String>("chain-name")
-                .expect("chain-name required")
-                .to_string();
-
-            let prkey: String = match launch_matches.get_one::<String>("key") {
-                Some(key) => key.to_string(),
+            let file_path = canonical_path(&args.file)?;
+            let metadata_id: String = match args.metadata_id {
+                Some(metadata_id) => metadata_id,
                 None => {
-                    if context.config.mamoru_private_key.is_empty() {
-                        eprintln!("Private key required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_private_key.clone()
+                    let deployments =
+                        Deployments::load(&file_path).or_cli_error(CliError::Config)?;
+                    match deployments.latest_metadata_id(&context.config.mamoru_profile) {
+                        Some(metadata_id) => metadata_id.to_string(),
+                        None => {
+                            return Err(CliError::Config("Metadata ID required".to_string()));
+                        }
                     }
                 }
             };
+            let chain = args.chain.resolve(context.config)?;
+            let organization_id = args.organization.resolve(context.config)?;
+
+            let agent = commands::agent::launch::launch_agent(
+                metadata_id.clone(),
+                chain.grpc,
+                chain.prkey,
+                args.chain_name,
+                &file_path,
+                chain.gas_limit,
+                chain.chain_id.clone(),
+            )
+            .await
+            .or_cli_error(CliError::Chain)?;
 
-            let gas_limit: String = match launch_matches.get_one::<String>("gas-limit") {
-                Some(gas_limit) => gas_limit.to_string(),
-                None => {
-                    if context.config.mamoru_gas_limit.is_empty() {
-                        eprintln!("Gas limit required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_gas_limit.clone()
-                    }
-                }
+            let result = PublishResult {
+                metadata_id,
+                chain_id: chain.chain_id,
+                agents: vec![agent],
+                ..Default::default()
             };
-            let gas_limit = gas_limit
-                .parse::<u64>()
-                .expect("gas limit must be a number");
-
-            let chain_id: String = match launch_matches.get_one::<String>("chain-id") {
-                Some(chain_id) => chain_id.to_string(),
-                None => {
-                    if context.config.mamoru_chain_id.is_empty() {
-                        eprintln!("Gas limit required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_chain_id.clone()
-                    }
-                }
+            let assigned = if remote {
+                assign_agents(&context, &result.agents, &organization_id).await
+            } else {
+                Ok(())
             };
-
-            let organization_id = get_organization_id(launch_matches, &context);
-
-            let publish_result = commands::agent::launch::launch_agent(
-                metadata_id,
-                grpc.clone(),
-                prkey,
-                chain_name,
+            record_deployments(
                 &file_path,
-                gas_limit,
-                chain_id,
-            )
-            .await;
-            if grpc != "http://localhost:9090" && grpc != "http://127.0.0.1:9090" {
-                println!("Assign agent to organization");
-                let token = context
-                    .cred_store
-                    .get("access_token")
-                    .expect("access_token required");
-
-                match publish_result {
-                    Ok(daemon_id) => {
-                        match register_daemon_to_organization(
-                            context.config.mamoru_graphql_url.as_str(),
-                            token,
-                            daemon_id.as_str(),
-                            organization_id.as_str(),
-                        )
-                        .await
-                        {
-                            Ok(_) => println!("Agent successfully registered to the organization."),
-                            Err(e) => println!("Error graphql: {:?}", e),
-                        }
-                    }
-                    Err(e) => println!("Error publish agent: {:?}", e),
-                }
-            }
+                context.config,
+                &result,
+                remote.then_some(organization_id.as_str()),
+            );
+            output::print_result(&result)?;
+            assigned?;
         }
 
-        if let Some(assign_matches) = agent_matches.subcommand_matches("assign") {
-            match check_auth(&mut context).await {
-                Ok(_) => (),
-                Err(e) => {
-                    eprintln!("Error: {:?}", e);
-                    std::process::exit(1);
-                }
-            };
+        Commands::Agent(AgentCommands::Assign(args)) => {
+            check_auth(&context).await?;
 
-            let organization_id = get_organization_id(assign_matches, &context);
+            let organization_id = args.organization.resolve(context.config)?;
 
-            let daemon_id = assign_matches
-                .get_one::<String>("agent-id")
-                .expect("agent-id required")
-                .to_string();
+            let daemon_id = get_agent_id(&args.deployment, &context)?;
 
-            let graphql_url = assign_matches
-                .get_one::<String>("graphql-url")
-                .map(|s| s.as_str());
-            let graphql_url = match graphql_url {
-                Some(url) => url.to_string(),
+            let graphql_url = match args.graphql_url {
+                Some(url) => url,
                 None => {
                     if context.config.mamoru_graphql_url.is_empty() {
-                        eprintln!("GraphQL URL required");
-                        std::process::exit(1);
+                        return Err(CliError::Config("GraphQL URL required".to_string()));
                     } else {
                         context.config.mamoru_graphql_url.clone()
                     }
@@ -430,160 +307,508 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             commands::agent::assign::assign_to_organization(
                 graphql_url,
-                daemon_id,
-                organization_id,
-                context.cred_store,
+                daemon_id.clone(),
+                organization_id.clone(),
+                &context.access_token().await?,
             )
             .await?;
+
+            update_deployments(&args.deployment.dir, |deployments| {
+                deployments.set_organization(&daemon_id, &organization_id);
+            });
+            output::print_result(&commands::agent::assign::AssignResult {
+                agent_id: daemon_id,
+                organization_id,
+            })?;
         }
 
-        if let Some(unregister_matches) = agent_matches.subcommand_matches("unregister") {
-            check_auth(&mut context).await?;
+        Commands::Agent(AgentCommands::Unregister(args)) => {
+            check_auth(&context).await?;
 
-            let daemon_id = unregister_matches
-                .get_one::<String>("agent-id")
-                .expect("agent-id required")
-                .to_string();
+            let daemon_id = get_agent_id(&args.deployment, &context)?;
+            let chain = args.chain.resolve(context.config)?;
 
-            let prkey: String = match unregister_matches.get_one::<String>("key") {
-                Some(key) => key.to_string(),
-                None => {
-                    if context.config.mamoru_private_key.is_empty() {
-                        eprintln!("Private key required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_private_key.clone()
-                    }
-                }
-            };
+            let response = commands::agent::unregister::unregister_agent(
+                chain.prkey,
+                chain.grpc,
+                chain.chain_id,
+                chain.gas_limit,
+                daemon_id.clone(),
+            )
+            .await
+            .or_cli_error(CliError::Chain)?;
+
+            update_deployments(&args.deployment.dir, |deployments| {
+                deployments.remove_agent(&daemon_id);
+            });
+            output::print_result(&commands::agent::unregister::UnregisterResult {
+                agent_id: response,
+            })?;
+        }
 
-            let grpc: String = match unregister_matches.get_one::<String>("grpc") {
-                Some(grpc) => grpc.to_string(),
-                None => {
-                    if context.config.mamoru_rpc_url.is_empty() {
-                        eprintln!("gRPC URL required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_rpc_url.clone()
-                    }
-                }
-            };
-            let gas_limit: String = match unregister_matches.get_one::<String>("gas-limit") {
-                Some(gas_limit) => gas_limit.to_string(),
-                None => {
-                    if context.config.mamoru_gas_limit.is_empty() {
-                        eprintln!("Gas limit required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_gas_limit.clone()
+        Commands::Agent(AgentCommands::Upgrade(args)) => {
+            let grpc = args.chain.grpc(context.config)?;
+            let remote = !cli::is_local_grpc(&grpc);
+            if remote {
+                status!("Checking auth");
+                check_auth(&context).await?;
+            }
+
+            let file_path = canonical_path(&args.file)?;
+            let chain = args.chain.resolve(context.config)?;
+
+            let (chain_name, previous_agent_id) = match (args.agent_id, args.chain_name) {
+                (Some(agent_id), Some(chain_name)) => (chain_name, agent_id),
+                (_, chain_name) => {
+                    let deployments =
+                        Deployments::load(&file_path).or_cli_error(CliError::Config)?;
+                    match deployments.find(&context.config.mamoru_profile, chain_name.as_deref()) {
+                        Ok(deployment) => {
+                            (deployment.chain_name.clone(), deployment.agent_id.clone())
+                        }
+                        Err(e) => {
+                            return Err(CliError::Config(format!("Agent ID required: {}", e)));
+                        }
                     }
                 }
             };
-            let gas_limit = gas_limit
-                .parse::<u64>()
-                .expect("gas limit must be a number");
 
-            let chain_id: String = match unregister_matches.get_one::<String>("chain-id") {
-                Some(chain_id) => chain_id.to_string(),
-                None => {
-                    if context.config.mamoru_chain_id.is_empty() {
-                        eprintln!("Chain ID required");
-                        std::process::exit(1);
-                    } else {
-                        context.config.mamoru_chain_id.clone()
-                    }
-                }
+            let organization_id = args.organization.resolve(context.config)?;
+            let wasm_file = wasm_file(&args.wasm, &file_path)?;
+
+            let result = commands::agent::upgrade::upgrade_agent(
+                chain.grpc,
+                chain.prkey,
+                chain_name,
+                previous_agent_id.clone(),
+                !args.new_metadata,
+                &context.config.mamoru_profile,
+                &file_path,
+                wasm_file.as_deref(),
+                args.optimize,
+                chain.gas_limit,
+                chain.chain_id,
+            )
+            .await
+            .or_cli_error(CliError::Chain)?;
+
+            let assigned = if remote {
+                assign_agents(&context, &result.published.agents, &organization_id).await
+            } else {
+                Ok(())
             };
+            record_deployments(
+                &file_path,
+                context.config,
+                &result.published,
+                remote.then_some(organization_id.as_str()),
+            );
+            if let Some(replaced_agent_id) = &result.replaced_agent_id {
+                update_deployments(&file_path, |deployments| {
+                    deployments.remove_agent(replaced_agent_id);
+                });
+            }
+            output::print_result(&result)?;
+            if result.replaced_agent_id.is_none() {
+                return Err(CliError::Chain(format!(
+                    "previous agent {} wasn't unregistered",
+                    previous_agent_id
+                )));
+            }
+            assigned?;
+        }
+
+        Commands::Deploy(args) => {
+            let grpc = args.chain.grpc(context.config)?;
+            let remote = !cli::is_local_grpc(&grpc);
+            if remote {
+                status!("Checking auth");
+                check_auth(&context).await?;
+            }
+
+            let deploy_file =
+                commands::deploy::DeployFile::read(&args.file).or_cli_error(CliError::Config)?;
 
-            match commands::agent::unregister::unregister_agent(
-                prkey, grpc, chain_id, gas_limit, daemon_id,
+            let plans = commands::deploy::build_plan(
+                &grpc,
+                &context.config.mamoru_profile,
+                &deploy_file,
+                args.optimize,
             )
             .await
+            .or_cli_error(CliError::Chain)?;
+            commands::deploy::print_plan(&plans)?;
+
+            let (create, upgrade, remove, _) = commands::deploy::plan_summary(&plans);
+            if args.plan {
+                return Ok(());
+            }
+            if create + upgrade + remove == 0 {
+                return commands::deploy::save_deploy_state(&deploy_file, &plans)
+                    .or_cli_error(CliError::Config);
+            }
+
+            if !args.yes
+                && !dialoguer::Confirm::new()
+                    .with_prompt("Do you want to apply this plan?")
+                    .default(false)
+                    .show_default(true)
+                    .interact()
+                    .or_cli_error(CliError::Config)?
             {
-                Ok(response) => println!("Success unregister agent: {}", response),
-                Err(e) => println!("Error unregister agent: {:?}", e),
+                return Ok(());
+            }
+
+            let chain = args.chain.resolve(context.config)?;
+            let organization_id = args.organization.optional(context.config);
+            let token = if remote {
+                Some(context.access_token().await?)
+            } else {
+                None
             };
+
+            let options = commands::deploy::ApplyOptions {
+                grpc: &chain.grpc,
+                prkey: &chain.prkey,
+                gas_limit: chain.gas_limit,
+                chain_id: &chain.chain_id,
+                profile: &context.config.mamoru_profile,
+                reuse_metadata: !args.new_metadata,
+                optimize: args.optimize,
+                organization_id: organization_id.as_deref(),
+                graphql: token
+                    .as_deref()
+                    .map(|token| (context.config.mamoru_graphql_url.as_str(), token)),
+            };
+            let report = commands::deploy::apply_plan(&plans, &options)
+                .await
+                .or_cli_error(CliError::Chain)?;
+            commands::deploy::save_deploy_state(&deploy_file, &plans)
+                .or_cli_error(CliError::Config)?;
+            output::print_result(&report)?;
+            if !report.errors.is_empty() {
+                let message = format!("{} deployment action(s) failed", report.errors.len());
+                // only organization assignments failed
+                if report.errors.len() == report.assignment_errors {
+                    return Err(CliError::Graphql(message));
+                }
+                return Err(CliError::Failed(message));
+            }
         }
-    }
-    if let Some(_logout_matches) = matches.subcommand_matches("logout") {
-        commands::logout::logout(&mut context);
-    }
 
-    if let Some(_login_matches) = matches.subcommand_matches("login") {
-        match dialoguer::Confirm::new()
-            .with_prompt("Do you want to create a new token?")
-            .default(false)
-            .show_default(true)
-            .interact()
-            .unwrap()
-        {
-            true => (),
-            false => std::process::exit(0),
-        };
-        match commands::login::login(&config).await {
-            Ok(resp) => {
-                let access_token = resp.access_token.clone().unwrap();
-                let refresh_token = resp.refresh_token.clone().unwrap_or_default();
-                println!();
-                println!("Access token received!");
-                if commands::login::save_tokens(&access_token, &refresh_token, &mut context)
-                    .is_err()
-                {
-                    eprintln!("Couldn't configure credentials.");
-                    std::process::exit(1);
+        Commands::Manifest(ManifestCommands::Init { grpc, force, file }) => {
+            let file_path = canonical_path(&file)?;
+            let grpc = grpc.or_else(|| {
+                Some(context.config.mamoru_rpc_url.clone()).filter(|url| !url.is_empty())
+            });
+
+            commands::manifest::init_manifest(&file_path, grpc.as_deref(), force)
+                .await
+                .or_cli_error(CliError::Manifest)?;
+        }
+
+        Commands::Auth(AuthCommands::Status) | Commands::Whoami => {
+            let claims = check_auth(&context).await?;
+            let tokens = context
+                .cred_store()
+                .await
+                .or_cli_error(CliError::Auth)?
+                .get(&context.namespace())
+                .await
+                .or_cli_error(CliError::Auth)?;
+            let refresh_token = tokens.and_then(|tokens| tokens.refresh).is_some();
+
+            let organizations = if context.config.mamoru_graphql_url.is_empty() {
+                None
+            } else {
+                let token = context.access_token().await?;
+                match list_organizations(&context.config.mamoru_graphql_url, &token).await {
+                    Ok(organizations) => Some(organizations),
+                    Err(e) => {
+                        status!(
+                            "{color_yellow}Warning{color_reset}: couldn't list organizations: {}",
+                            e
+                        );
+                        None
+                    }
                 }
+            };
+
+            output::print_result(&commands::auth::AuthStatus::new(
+                &context.config.mamoru_profile,
+                &claims,
+                refresh_token,
+                organizations,
+                chrono::Utc::now().timestamp(),
+            ))?;
+        }
+
+        Commands::Logout(args) => {
+            let result = commands::logout::logout(&context, args.all)
+                .await
+                .or_cli_error(CliError::Auth)?;
+            output::print_result(&result)?;
+
+            let failed = result.failed_revocations();
+            if !failed.is_empty() {
+                let profiles = failed
+                    .iter()
+                    .map(|profile| profile.profile.as_str())
+                    .collect::<Vec<_>>();
+                return Err(CliError::Auth(format!(
+                    "logged out locally, but couldn't revoke the refresh token of {}",
+                    profiles.join(", ")
+                )));
             }
-            Err(e) => println!("Error logging in: {}", e),
+        }
+
+        Commands::Completions { shell } => {
+            let bin = env::current_exe()?;
+            completion::write_registration(shell, &bin, &mut std::io::stdout())?;
+        }
+
+        Commands::Man { dir } => match dir {
+            Some(dir) => {
+                fs::create_dir_all(&dir)?;
+                clap_mangen::generate_to(Cli::command(), &dir)?;
+                status!("Man pages written to {}", dir.display());
+            }
+            None => clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout())?,
+        },
+
+        Commands::Login(args) => {
+            let (resp, grant) = if args.client_credentials {
+                let resp = request_client_credentials_token(&config)
+                    .await
+                    .or_cli_error(CliError::Auth)?;
+                (resp, Some(CLIENT_CREDENTIALS_GRANT))
+            } else {
+                match dialoguer::Confirm::new()
+                    .with_prompt("Do you want to create a new token?")
+                    .default(false)
+                    .show_default(true)
+                    .interact()
+                    .or_cli_error(CliError::Config)?
+                {
+                    true => (),
+                    false => return Ok(()),
+                };
+                let resp = if args.browser {
+                    commands::login::login_browser(&config, args.port).await
+                } else {
+                    commands::login::login(&config).await
+                };
+                (resp.or_cli_error(CliError::Auth)?, None)
+            };
+            if resp.access_token.is_none() {
+                return Err(CliError::Auth("no access token received".to_string()));
+            }
+            status!("Access token received!");
+            commands::login::save_tokens(&resp, grant, &context)
+                .await
+                .map_err(|e| CliError::Auth(format!("couldn't configure credentials: {}", e)))?;
+            output::print_result(&commands::login::LoginResult::new(
+                &config.mamoru_profile,
+                &resp,
+            ))?;
         }
     }
 
     Ok(())
 }
 
-async fn check_auth<T>(
-    context: &mut CommandContext<'_, T>,
-) -> Result<(), Box<dyn std::error::Error>>
-where
-    T: CredStore + Send + Sync + 'static,
-{
+/// Returns the claims of the verified access token, refreshed when it expired.
+async fn check_auth<T: CredStore>(context: &CommandContext<'_, T>) -> Result<Claims, CliError> {
     let access_token = match get_token(context).await {
         Ok(token) => match token {
             Some(token) => token,
             None => {
-                eprintln!("You must login first, please run 'mamorurs-cli login'.");
-                std::process::exit(1);
+                return Err(CliError::Auth(
+                    "you must login first, please run 'mamorurs-cli login'".to_string(),
+                ));
             }
         },
         Err(e) => {
-            eprintln!("Couldn't get credentials: {}.  Try to login again.", e);
-            std::process::exit(1);
+            return Err(CliError::Auth(format!(
+                "couldn't get credentials: {}. Try to login again",
+                e
+            )));
         }
     };
     // verify token
-    let verifier = JwtVerifier::new(&context.config.mamoru_cli_auth0_domain)
+    let scopes = context
+        .config
+        .mamoru_cli_auth0_scopes
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let mut verifier = JwtVerifier::new(&context.config.mamoru_cli_auth0_domain)
         .validate_aud(&context.config.mamoru_cli_auth0_audience)
-        .use_cache(true)
-        .build();
-    let _ver_res = verifier.verify::<Claims>(access_token.as_str()).await?;
+        .algorithms(&AUTH0_ALGORITHMS)
+        .leeway(AUTH0_LEEWAY)
+        .require_scopes(&scopes)
+        .use_cache(true);
+    if let Some(issuer) = &context.config.mamoru_cli_auth0_issuer {
+        verifier = verifier.issuer(issuer);
+    }
+    if let Some(dir) = mamoru_dir() {
+        verifier = verifier.cache_file(dir.join(JWKS_CACHE));
+    }
+    let token_data = verifier
+        .build()
+        .verify::<Claims>(access_token.as_str())
+        .await
+        .or_cli_error(CliError::Auth)?;
 
-    Ok(())
+    Ok(token_data.claims)
 }
 
-fn get_organization_id(
-    matcher: &ArgMatches,
-    context: &CommandContext<'_, impl CredStore>,
-) -> String {
-    let organization_id = matcher.get_one::<String>("organization-id");
-
-    match organization_id {
-        Some(organization_id) => organization_id.to_string(),
-        None => {
-            if context.config.mamoru_organization_id.is_empty() {
-                eprintln!("Organization ID required");
-                std::process::exit(1);
-            } else {
-                context.config.mamoru_organization_id.clone()
-            }
+/// Opens the configured credential store, or the tokens of the environment when they're set.
+async fn open_cred_store(config: &Config, file: Credentials) -> Result<Store, io::Error> {
+    let namespace = Namespace::new(&config.mamoru_profile, &config.mamoru_cli_auth0_audience);
+    let (store, fallback) = match EnvStore::from_env(&namespace) {
+        Some(store) => (Store::Env(store), None),
+        None => Store::open(&config.mamoru_cred_store, file, credentials_passphrase).await?,
+    };
+
+    if let Some(fallback) = fallback {
+        status!(
+            "{color_yellow}Warning{color_reset}: {} credential store unavailable ({}), using the credentials file",
+            fallback.requested,
+            fallback.reason
+        );
+    }
+    // tokens saved before they were namespaced belong to the current profile
+    store.migrate(&namespace).await?;
+
+    Ok(store)
+}
+
+/// Passphrase of the encrypted credentials file, from `MAMORU_CRED_PASSPHRASE` or asked for.
+fn credentials_passphrase() -> Result<String, io::Error> {
+    if let Some(passphrase) = EncryptedFileStore::passphrase_from_env() {
+        return Ok(passphrase);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} required to read the encrypted credentials",
+                PASSPHRASE_ENV
+            ),
+        ));
+    }
+
+    dialoguer::Password::new()
+        .with_prompt("Credentials passphrase")
+        .interact()
+        .map_err(io::Error::other)
+}
+
+/// Assigns the agents to the organization, every agent is tried before a failure is reported.
+async fn assign_agents<T: CredStore>(
+    context: &CommandContext<'_, T>,
+    agents: &[PublishedAgent],
+    organization_id: &str,
+) -> Result<(), CliError> {
+    status!("Assign agent to organization");
+    let token = context.access_token().await?;
+
+    let mut failed = vec![];
+    for agent in agents {
+        if let Err(e) = register_daemon_to_organization(
+            context.config.mamoru_graphql_url.as_str(),
+            &token,
+            agent.daemon_id.as_str(),
+            organization_id,
+        )
+        .await
+        {
+            eprintln!("Error graphql: {:?}", e);
+            failed.push(agent.daemon_id.as_str());
         }
     }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::Graphql(format!(
+            "couldn't assign {} to organization {}",
+            failed.join(", "),
+            organization_id
+        )))
+    }
+}
+
+/// Fails when the agent wasn't registered on some of the chains.
+fn check_failed_chains(result: &PublishResult) -> Result<(), CliError> {
+    if result.failed.is_empty() {
+        return Ok(());
+    }
+
+    let chains = result
+        .failed
+        .iter()
+        .map(|failed| failed.chain_name.as_str())
+        .collect::<Vec<_>>();
+    Err(CliError::Chain(format!(
+        "agent wasn't registered on {}",
+        chains.join(", ")
+    )))
+}
+
+fn canonical_path(path: &Path) -> Result<PathBuf, CliError> {
+    path.canonicalize()
+        .map_err(|e| CliError::Config(format!("invalid path {}: {}", path.display(), e)))
+}
+
+/// Builds the agent when `--build` is set, otherwise returns the `--wasm` file if any.
+fn wasm_file(args: &WasmArgs, dir_path: &Path) -> Result<Option<PathBuf>, CliError> {
+    if args.build {
+        Ok(Some(
+            commands::agent::build::build_agent(dir_path, args.target.as_deref())
+                .or_cli_error(CliError::Wasm)?,
+        ))
+    } else {
+        Ok(args.wasm.clone())
+    }
+}
+
+fn get_agent_id(
+    args: &DeploymentArgs,
+    context: &CommandContext<'_, impl CredStore>,
+) -> Result<String, CliError> {
+    if let Some(agent_id) = &args.agent_id {
+        return Ok(agent_id.to_string());
+    }
+
+    let deployments = Deployments::load(&args.dir)
+        .map_err(|e| CliError::Config(format!("couldn't read deployment state: {}", e)))?;
+
+    match deployments.find(&context.config.mamoru_profile, args.chain_name.as_deref()) {
+        Ok(deployment) => Ok(deployment.agent_id.clone()),
+        Err(e) => Err(CliError::Config(format!("Agent ID required: {}", e))),
+    }
+}
+
+/// Records the published agents in the deployment state and the completion history, reporting
+/// failures without aborting the command.
+fn record_deployments(
+    dir_path: &Path,
+    config: &Config,
+    result: &PublishResult,
+    organization_id: Option<&str>,
+) {
+    if let Err(e) =
+        deployments::record_deployments(dir_path, &config.mamoru_profile, result, organization_id)
+    {
+        eprintln!("Couldn't update deployment state: {}", e);
+    }
+    completion::update_history(|history| history.remember_agents(result));
+}
+
+fn update_deployments(dir_path: &Path, update: impl FnOnce(&mut Deployments)) {
+    if let Err(e) = deployments::update_deployments(dir_path, update) {
+        eprintln!("Couldn't update deployment state: {}", e);
+    }
 }
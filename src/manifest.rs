@@ -1,7 +1,10 @@
-use serde::Deserialize;
-use std::{collections::HashMap, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct Manifest {
     pub name: String,
     pub version: HashMap<String, String>,
@@ -15,9 +18,10 @@ pub struct Manifest {
     #[serde(rename = "chains")]
     pub supported_chains: Vec<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub parameters: Option<Vec<ManifestParameter>>,
 }
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct ManifestParameter {
     #[serde(rename = "type")]
     pub type_: String,
@@ -26,23 +30,37 @@ pub struct ManifestParameter {
     pub description: String,
     #[serde(rename = "defaultValue")]
     pub default_value: String,
-    #[serde(rename = "requiredFor")]
+    #[serde(rename = "requiredFor", skip_serializing_if = "Option::is_none")]
     pub required_for: Option<Vec<String>>,
-    #[serde(rename = "hiddenFor")]
+    #[serde(rename = "hiddenFor", skip_serializing_if = "Option::is_none")]
     pub hidden_for: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub min: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub max: Option<String>,
-    #[serde(rename = "minLen")]
+    #[serde(rename = "minLen", skip_serializing_if = "Option::is_none")]
     pub min_len: Option<u32>,
-    #[serde(rename = "maxLen")]
+    #[serde(rename = "maxLen", skip_serializing_if = "Option::is_none")]
     pub max_len: Option<u32>,
 }
 
+/// Writes `manifest` to `manifest.yaml` in `dir_path` and returns the file path.
+pub fn write_manifest_file(
+    dir_path: &Path,
+    manifest: &Manifest,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let manifest_path = dir_path.join("manifest.yaml");
+    std::fs::write(&manifest_path, serde_yaml::to_string(manifest)?)?;
+
+    Ok(manifest_path)
+}
+
 pub fn read_manifest_file(dir_path: &Path) -> Option<Manifest> {
     let manifest_path = dir_path.join("manifest.yaml");
     if !manifest_path.exists() {
-        println!("Manifest file not found: {}", manifest_path.display());
+        eprintln!("Manifest file not found: {}", manifest_path.display());
         return None;
     }
 
@@ -50,7 +68,7 @@ pub fn read_manifest_file(dir_path: &Path) -> Option<Manifest> {
     match manifest {
         Ok(manifest) => Some(manifest),
         Err(e) => {
-            println!("Error reading manifest file: {}", e);
+            eprintln!("Error reading manifest file: {}", e);
             None
         }
     }
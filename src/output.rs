@@ -0,0 +1,215 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use spinners::{Spinner, Spinners};
+use std::{io::IsTerminal, sync::OnceLock};
+
+/// Format of command results, set with the global `--output` flag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable table, with colors on a terminal.
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Sets the output format, called once when the arguments are parsed.
+pub fn init(format: OutputFormat) {
+    _ = FORMAT.set(format);
+}
+
+pub fn format() -> OutputFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+/// Colors and spinners are only used for table output on a terminal, and when `NO_COLOR` is unset.
+pub fn is_interactive() -> bool {
+    format() == OutputFormat::Table
+        && std::io::stdout().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Prints a progress message.
+///
+/// Colors are stripped when the output isn't interactive. With JSON or YAML output messages go
+/// to stderr, so stdout only contains the result document.
+pub fn status(message: &str) {
+    if is_interactive() {
+        println!("{}", message);
+    } else if format() == OutputFormat::Table {
+        println!("{}", strip_ansi(message));
+    } else {
+        eprintln!("{}", strip_ansi(message));
+    }
+}
+
+/// `println!` for progress messages, see [`status`].
+#[macro_export]
+macro_rules! status {
+    () => {
+        $crate::output::status("")
+    };
+    ($($arg:tt)*) => {
+        $crate::output::status(&format!($($arg)*))
+    };
+}
+
+/// Spinner shown while waiting for the chain, a plain message when the output isn't interactive.
+pub struct Progress(Option<Spinner>);
+
+impl Progress {
+    pub fn start(message: impl Into<String>) -> Self {
+        let message = message.into();
+        if is_interactive() {
+            Progress(Some(Spinner::new(Spinners::Triangle, message)))
+        } else {
+            status(&message);
+            Progress(None)
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut spinner) = self.0.take() {
+            spinner.stop();
+            println!();
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Rows of a result rendered as a table.
+#[derive(Debug, Default, PartialEq)]
+pub struct Table {
+    pub headers: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: Vec<&'static str>) -> Self {
+        Table {
+            headers,
+            rows: vec![],
+        }
+    }
+
+    pub fn row(mut self, row: Vec<String>) -> Self {
+        self.rows.push(row);
+        self
+    }
+
+    /// Aligns the columns, cells may contain colors.
+    pub fn render(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| header.len()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                let width = strip_ansi(cell).chars().count();
+                match widths.get_mut(i) {
+                    Some(max) => *max = (*max).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+
+        let headers = self.headers.iter().map(|header| header.to_string());
+        std::iter::once(headers.collect::<Vec<_>>())
+            .chain(self.rows.iter().cloned())
+            .map(|row| {
+                let last = row.len().saturating_sub(1);
+                row.iter()
+                    .enumerate()
+                    .map(|(i, cell)| {
+                        let padding = if i == last {
+                            0
+                        } else {
+                            widths[i] - strip_ansi(cell).chars().count()
+                        };
+                        format!("{}{}", cell, " ".repeat(padding))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Result of a command, printed with [`print_result`].
+pub trait Render: Serialize {
+    fn table(&self) -> Table;
+}
+
+/// Prints a command result in the selected output format.
+pub fn print_result<T: Render + ?Sized>(result: &T) -> Result<(), Box<dyn std::error::Error>> {
+    match format() {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(result)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(result)?),
+        OutputFormat::Table => {
+            let table = result.table().render();
+            if is_interactive() {
+                println!("{}", table);
+            } else {
+                println!("{}", strip_ansi(&table));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes ANSI escape sequences like `\x1b[32m`.
+pub fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inline_colorization::{color_green, color_reset};
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi(&format!("AgentId: {color_green}agent1{color_reset}")),
+            "AgentId: agent1"
+        );
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn test_table_render() {
+        let table = Table::new(vec!["CHAIN", "AGENT ID"])
+            .row(vec![
+                format!("{color_green}SUI_MAINNET{color_reset}"),
+                "agent1".to_string(),
+            ])
+            .row(vec!["ETH".to_string(), "agent2".to_string()]);
+
+        assert_eq!(
+            strip_ansi(&table.render()),
+            "CHAIN        AGENT ID\nSUI_MAINNET  agent1\nETH          agent2"
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    fs,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use wasm_encoder::{ComponentSectionId, Encode, RawSection, Section};
+use wasmparser::{BinaryReaderError, Encoding, Parser, Payload};
+
+use crate::status;
+
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// `wasm-opt` flags of the features a module can declare in its `target_features` section.
+const WASM_OPT_FEATURES: [(&str, &str); 15] = [
+    ("atomics", "--enable-threads"),
+    ("bulk-memory", "--enable-bulk-memory"),
+    ("exception-handling", "--enable-exception-handling"),
+    ("extended-const", "--enable-extended-const"),
+    ("gc", "--enable-gc"),
+    ("memory64", "--enable-memory64"),
+    ("multimemory", "--enable-multimemory"),
+    ("multivalue", "--enable-multivalue"),
+    ("mutable-globals", "--enable-mutable-globals"),
+    ("nontrapping-fptoint", "--enable-nontrapping-float-to-int"),
+    ("reference-types", "--enable-reference-types"),
+    ("relaxed-simd", "--enable-relaxed-simd"),
+    ("sign-ext", "--enable-sign-ext"),
+    ("simd128", "--enable-simd"),
+    ("tail-call", "--enable-tail-call"),
+];
+
+/// Shrinks an agent component before it's registered.
+///
+/// Custom sections (names, debug info, producers) are removed from the component and every
+/// nested core module. When `wasm-opt` is installed the core modules are also optimized for size,
+/// with only the features they declare in their `target_features` section enabled.
+pub fn optimize_wasm(wasm: &[u8]) -> Result<Vec<u8>, BinaryReaderError> {
+    if Command::new("wasm-opt").arg("--version").output().is_ok() {
+        rewrite(wasm, &run_wasm_opt)
+    } else {
+        status!("wasm-opt not found, only custom sections are stripped");
+        strip_custom_sections(wasm)
+    }
+}
+
+/// Removes custom sections from a module or component, including nested ones.
+pub fn strip_custom_sections(wasm: &[u8]) -> Result<Vec<u8>, BinaryReaderError> {
+    rewrite(wasm, &|module, _| module)
+}
+
+/// Transforms a core module, given the features of its `target_features` section.
+type OptimizeModule<'a> = dyn Fn(Vec<u8>, &[String]) -> Vec<u8> + 'a;
+
+/// Copies all non-custom sections, passing every core module and its target features through
+/// `optimize_module`.
+fn rewrite(wasm: &[u8], optimize_module: &OptimizeModule) -> Result<Vec<u8>, BinaryReaderError> {
+    let mut output: Vec<u8> = vec![];
+    let mut features: Vec<String> = vec![];
+    let mut stack: Vec<(Vec<u8>, Vec<String>)> = vec![];
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload?;
+        match &payload {
+            Payload::Version { encoding, .. } => {
+                output.extend_from_slice(match encoding {
+                    Encoding::Component => &wasm_encoder::Component::HEADER,
+                    Encoding::Module => &wasm_encoder::Module::HEADER,
+                });
+                continue;
+            }
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => {
+                stack.push((std::mem::take(&mut output), std::mem::take(&mut features)));
+                continue;
+            }
+            Payload::End(_) => {
+                let is_module = output.starts_with(&wasm_encoder::Module::HEADER);
+                if is_module {
+                    output = optimize_module(output, &features);
+                }
+
+                let (mut parent, parent_features) = match stack.pop() {
+                    Some(parent) => parent,
+                    None => break,
+                };
+                features = parent_features;
+                parent.push(if is_module {
+                    ComponentSectionId::CoreModule as u8
+                } else {
+                    ComponentSectionId::Component as u8
+                });
+                output.encode(&mut parent);
+                output = parent;
+                continue;
+            }
+            Payload::CustomSection(reader) => {
+                if reader.name() == "target_features" {
+                    features = target_features(reader.data()).unwrap_or_default();
+                }
+                continue;
+            }
+            _ => (),
+        }
+
+        if let Some((id, range)) = payload.as_section() {
+            RawSection {
+                id,
+                data: &wasm[range],
+            }
+            .append_to(&mut output);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Returns the features a `target_features` section marks as used (`+`) or required (`=`).
+fn target_features(data: &[u8]) -> Option<Vec<String>> {
+    let mut data = data;
+    let count = read_leb_u32(&mut data)?;
+    let mut features = vec![];
+    for _ in 0..count {
+        let (&prefix, rest) = data.split_first()?;
+        data = rest;
+        let len = read_leb_u32(&mut data)? as usize;
+        if data.len() < len {
+            return None;
+        }
+        let (name, rest) = data.split_at(len);
+        data = rest;
+        if prefix == b'+' || prefix == b'=' {
+            features.push(String::from_utf8(name.to_vec()).ok()?);
+        }
+    }
+
+    Some(features)
+}
+
+fn read_leb_u32(data: &mut &[u8]) -> Option<u32> {
+    let mut result: u32 = 0;
+    for shift in (0..35).step_by(7) {
+        let (&byte, rest) = data.split_first()?;
+        *data = rest;
+        result |= u32::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+
+    None
+}
+
+/// `wasm-opt` feature flags: the MVP and the declared features, which the module can only use.
+fn wasm_opt_feature_flags(features: &[String]) -> Vec<&'static str> {
+    let mut flags = vec!["--mvp-features"];
+    flags.extend(
+        WASM_OPT_FEATURES
+            .iter()
+            .filter(|(feature, _)| features.iter().any(|declared| declared == feature))
+            .map(|(_, flag)| *flag),
+    );
+
+    flags
+}
+
+/// Runs `wasm-opt -Oz` on a core module, keeping the original when it fails or doesn't help.
+fn run_wasm_opt(module: Vec<u8>, features: &[String]) -> Vec<u8> {
+    match try_wasm_opt(&module, features) {
+        Ok(optimized) if optimized.len() < module.len() => optimized,
+        Ok(_) => module,
+        Err(e) => {
+            status!("wasm-opt failed, keeping the module unoptimized: {}", e);
+            module
+        }
+    }
+}
+
+fn try_wasm_opt(module: &[u8], features: &[String]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let id = format!(
+        "mamoru-{}-{}",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let input_path = std::env::temp_dir().join(format!("{}.wasm", id));
+    let output_path = std::env::temp_dir().join(format!("{}.opt.wasm", id));
+    fs::write(&input_path, module)?;
+
+    let result = Command::new("wasm-opt")
+        .arg("-Oz")
+        .args(wasm_opt_feature_flags(features))
+        .args(["--strip-debug", "--strip-producers"])
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .output();
+
+    let optimized = match result {
+        Ok(output) if output.status.success() => fs::read(&output_path).map_err(|e| e.into()),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().into()),
+        Err(e) => Err(e.into()),
+    };
+
+    _ = fs::remove_file(&input_path);
+    _ = fs::remove_file(&output_path);
+
+    optimized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_MODULE: &[u8] = b"\0asm\x01\0\0\0";
+    const EMPTY_COMPONENT: &[u8] = b"\0asm\x0d\0\x01\0";
+    const CUSTOM_SECTION: &[u8] = &[0x00, 0x06, 0x04, b'n', b'a', b'm', b'e', 0xff];
+
+    #[test]
+    fn test_strip_module_custom_sections() {
+        let module = [EMPTY_MODULE, CUSTOM_SECTION].concat();
+
+        assert_eq!(strip_custom_sections(&module).unwrap(), EMPTY_MODULE);
+    }
+
+    #[test]
+    fn test_strip_nested_custom_sections() {
+        let nested_module = [EMPTY_MODULE, CUSTOM_SECTION].concat();
+        let mut component = [EMPTY_COMPONENT, CUSTOM_SECTION].concat();
+        component.push(ComponentSectionId::CoreModule as u8);
+        nested_module.encode(&mut component);
+
+        let mut expected = EMPTY_COMPONENT.to_vec();
+        expected.push(ComponentSectionId::CoreModule as u8);
+        EMPTY_MODULE.encode(&mut expected);
+
+        assert_eq!(strip_custom_sections(&component).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_target_features() {
+        let mut section = vec![3];
+        for (prefix, name) in [(b'+', "sign-ext"), (b'-', "simd128"), (b'=', "bulk-memory")] {
+            section.push(prefix);
+            name.encode(&mut section);
+        }
+        let features = target_features(&section).unwrap();
+
+        assert_eq!(features, vec!["sign-ext", "bulk-memory"]);
+        assert_eq!(
+            wasm_opt_feature_flags(&features),
+            vec![
+                "--mvp-features",
+                "--enable-bulk-memory",
+                "--enable-sign-ext"
+            ]
+        );
+        assert_eq!(wasm_opt_feature_flags(&[]), vec!["--mvp-features"]);
+        assert_eq!(target_features(&[2, b'+']), None);
+    }
+
+    #[test]
+    fn test_rewrite_passes_target_features() {
+        let mut section = vec![1, b'+'];
+        "mutable-globals".encode(&mut section);
+        let mut module = EMPTY_MODULE.to_vec();
+        wasm_encoder::CustomSection {
+            name: "target_features".into(),
+            data: section.into(),
+        }
+        .append_to(&mut module);
+        let mut component = EMPTY_COMPONENT.to_vec();
+        component.push(ComponentSectionId::CoreModule as u8);
+        module.encode(&mut component);
+
+        let features = std::cell::RefCell::new(vec![]);
+        rewrite(&component, &|module, module_features| {
+            features.borrow_mut().extend_from_slice(module_features);
+            module
+        })
+        .unwrap();
+
+        assert_eq!(features.into_inner(), vec!["mutable-globals"]);
+    }
+}
@@ -0,0 +1,241 @@
+use wasmparser::{Parser, Payload, Validator, WasmFeatures};
+
+/// Largest wasm module accepted by the validation chain.
+pub const MAX_WASM_SIZE: usize = 10 * 1024 * 1024;
+
+/// Interfaces of the `mamoru:agent` world (see `wit/agent.wit`) every agent must export.
+pub const REQUIRED_EXPORTS: [&str; 1] = ["mamoru:agent/handler"];
+
+/// Import prefixes agents are allowed to use.
+///
+/// The WASI preview 1 adapter always imports the filesystem interfaces, the runtime doesn't
+/// preopen any directory though.
+pub const ALLOWED_IMPORTS: [&str; 6] = [
+    "mamoru:agent/",
+    "wasi:cli/",
+    "wasi:io/",
+    "wasi:clocks/",
+    "wasi:random/",
+    "wasi:filesystem/",
+];
+
+/// Top-level imports and exports of a component.
+#[derive(Debug, Default)]
+pub struct ComponentInfo {
+    pub imports: Vec<String>,
+    pub exports: Vec<String>,
+}
+
+/// Checks the wasm module is a valid Mamoru agent component before it's uploaded.
+pub fn validate_agent_wasm(wasm: &[u8]) -> Result<ComponentInfo, Box<dyn std::error::Error>> {
+    check_size(wasm.len())?;
+
+    if !Parser::is_component(wasm) {
+        return Err(
+            "wasm file is not a component, build the agent with `cargo-component build --release`"
+                .into(),
+        );
+    }
+
+    Validator::new_with_features(WasmFeatures::default() | WasmFeatures::COMPONENT_MODEL)
+        .validate_all(wasm)
+        .map_err(|e| format!("invalid wasm component: {}", e))?;
+
+    let info = component_info(wasm)?;
+
+    let missing = missing_exports(&info.exports);
+    if !missing.is_empty() {
+        return Err(format!(
+            "component doesn't implement the Mamoru agent world, missing exports: {}",
+            missing.join(", ")
+        )
+        .into());
+    }
+
+    let forbidden = forbidden_imports(&info.imports);
+    if !forbidden.is_empty() {
+        return Err(format!(
+            "component uses imports that agents are not allowed to use: {}",
+            forbidden.join(", ")
+        )
+        .into());
+    }
+
+    Ok(info)
+}
+
+fn check_size(size: usize) -> Result<(), String> {
+    if size > MAX_WASM_SIZE {
+        return Err(format!(
+            "wasm file is too large: {:.2} MiB, the maximum is {} MiB",
+            size as f64 / (1024.0 * 1024.0),
+            MAX_WASM_SIZE / (1024 * 1024)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Collects the imports and exports of the outer component, nested ones are skipped.
+fn component_info(wasm: &[u8]) -> Result<ComponentInfo, wasmparser::BinaryReaderError> {
+    let mut info = ComponentInfo::default();
+    let mut depth = 0;
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            Payload::ComponentImportSection(reader) if depth == 0 => {
+                for import in reader {
+                    info.imports.push(import?.name.0.to_string());
+                }
+            }
+            Payload::ComponentExportSection(reader) if depth == 0 => {
+                for export in reader {
+                    info.exports.push(export?.name.0.to_string());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok(info)
+}
+
+fn missing_exports(exports: &[String]) -> Vec<&'static str> {
+    REQUIRED_EXPORTS
+        .into_iter()
+        .filter(|required| {
+            !exports
+                .iter()
+                .any(|export| export_matches(export, required))
+        })
+        .collect()
+}
+
+/// Matches `mamoru:agent/handler` and versioned names like `mamoru:agent/handler@0.1.0`.
+fn export_matches(export: &str, required: &str) -> bool {
+    export
+        .strip_prefix(required)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('@'))
+}
+
+fn forbidden_imports(imports: &[String]) -> Vec<String> {
+    imports
+        .iter()
+        .filter(|import| {
+            !ALLOWED_IMPORTS
+                .iter()
+                .any(|allowed| import.starts_with(allowed))
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_encoder::{
+        Component, ComponentExportKind, ComponentExportSection, ComponentImportSection,
+        ComponentInstanceSection, ComponentTypeRef, ComponentTypeSection, InstanceType,
+    };
+
+    const CORE_MODULE: &[u8] = b"\0asm\x01\0\0\0";
+    const EMPTY_COMPONENT: &[u8] = b"\0asm\x0d\0\x01\0";
+
+    /// Component importing and exporting empty interface instances with the given names.
+    fn component(imports: &[&str], exports: &[&str]) -> Vec<u8> {
+        let mut component = Component::new();
+
+        let mut types = ComponentTypeSection::new();
+        types.instance(&InstanceType::new());
+        component.section(&types);
+        let mut import_section = ComponentImportSection::new();
+        for name in imports {
+            import_section.import(name, ComponentTypeRef::Instance(0));
+        }
+        component.section(&import_section);
+
+        let mut instances = ComponentInstanceSection::new();
+        for _ in exports {
+            instances.export_items([]);
+        }
+        component.section(&instances);
+        let mut export_section = ComponentExportSection::new();
+        for (index, name) in exports.iter().enumerate() {
+            let instance = (imports.len() + index) as u32;
+            export_section.export(name, ComponentExportKind::Instance, instance, None);
+        }
+        component.section(&export_section);
+
+        component.finish()
+    }
+
+    #[test]
+    fn test_validate_agent_wasm() {
+        let wasm = component(
+            &["mamoru:agent/host@0.1.0", "wasi:io/streams@0.2.0"],
+            &["mamoru:agent/handler@0.1.0"],
+        );
+        let info = validate_agent_wasm(&wasm).unwrap();
+        assert_eq!(
+            info.imports,
+            vec!["mamoru:agent/host@0.1.0", "wasi:io/streams@0.2.0"]
+        );
+        assert_eq!(info.exports, vec!["mamoru:agent/handler@0.1.0"]);
+    }
+
+    #[test]
+    fn test_validate_invalid_agent_wasm() {
+        let err = validate_agent_wasm(CORE_MODULE).unwrap_err();
+        assert!(err.to_string().contains("not a component"));
+
+        let err = validate_agent_wasm(EMPTY_COMPONENT).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("missing exports: mamoru:agent/handler"));
+
+        let wasm = component(&["mamoru:agent/host@0.1.0"], &["mamoru:agent/other@0.1.0"]);
+        let err = validate_agent_wasm(&wasm).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("missing exports: mamoru:agent/handler"));
+
+        let wasm = component(&["wasi:sockets/tcp@0.2.0"], &["mamoru:agent/handler@0.1.0"]);
+        let err = validate_agent_wasm(&wasm).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("not allowed to use: wasi:sockets/tcp@0.2.0"));
+
+        let err = validate_agent_wasm(&vec![0; MAX_WASM_SIZE + 1]).unwrap_err();
+        assert!(err.to_string().contains("too large"));
+    }
+
+    #[test]
+    fn test_missing_exports() {
+        assert!(missing_exports(&["mamoru:agent/handler@0.1.0".to_string()]).is_empty());
+        assert!(missing_exports(&["mamoru:agent/handler".to_string()]).is_empty());
+        assert_eq!(
+            missing_exports(&["mamoru:agent/handler-v2".to_string()]),
+            vec!["mamoru:agent/handler"]
+        );
+    }
+
+    #[test]
+    fn test_forbidden_imports() {
+        let imports = vec![
+            "mamoru:agent/host@0.1.0".to_string(),
+            "wasi:io/streams@0.2.0".to_string(),
+            "wasi:sockets/tcp@0.2.0".to_string(),
+            "wasi:http/outgoing-handler@0.2.0".to_string(),
+        ];
+
+        assert_eq!(
+            forbidden_imports(&imports),
+            vec![
+                "wasi:sockets/tcp@0.2.0".to_string(),
+                "wasi:http/outgoing-handler@0.2.0".to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,42 @@
+#[allow(warnings)]
+mod bindings;
+
+use bindings::exports::mamoru::agent::handler::Guest;
+use bindings::mamoru::agent::host::{self, Incident, IncidentSeverity};
+use serde_json::Value;
+
+struct Component;
+
+impl Guest for Component {
+    fn handle_transaction(tx: String) {
+        let tx: Value = match serde_json::from_str(&tx) {
+            Ok(tx) => tx,
+            Err(e) => {
+                host::log(&format!("invalid transaction: {}", e));
+                return;
+            }
+        };
+
+        let sender = tx["sender"].as_str().unwrap_or_default();
+        let watched_sender = host::parameter("sender").unwrap_or_default();
+        if !watched_sender.is_empty() && watched_sender != sender {
+            return;
+        }
+
+        if tx["success"].as_bool() == Some(false) {
+            host::report(&Incident {
+                severity: IncidentSeverity::Warning,
+                message: format!(
+                    "Transaction failed: {}",
+                    tx["vm_status"].as_str().unwrap_or("unknown error")
+                ),
+                address: sender.to_string(),
+                data: tx["hash"].as_str().unwrap_or_default().as_bytes().to_vec(),
+            });
+        }
+    }
+
+    fn handle_block(_block: String) {}
+}
+
+bindings::export!(Component with_types_in bindings);
@@ -0,0 +1,41 @@
+#[allow(warnings)]
+mod bindings;
+
+use bindings::exports::mamoru::agent::handler::Guest;
+use bindings::mamoru::agent::host::{self, Incident, IncidentSeverity};
+use serde_json::Value;
+
+struct Component;
+
+impl Guest for Component {
+    fn handle_transaction(tx: String) {
+        let tx: Value = match serde_json::from_str(&tx) {
+            Ok(tx) => tx,
+            Err(e) => {
+                host::log(&format!("invalid transaction: {}", e));
+                return;
+            }
+        };
+
+        let threshold = host::parameter("threshold")
+            .and_then(|threshold| threshold.parse::<u128>().ok())
+            .unwrap_or(u128::MAX);
+        let value = tx["value"]
+            .as_str()
+            .and_then(|value| u128::from_str_radix(value.trim_start_matches("0x"), 16).ok())
+            .unwrap_or_default();
+
+        if value >= threshold {
+            host::report(&Incident {
+                severity: IncidentSeverity::Alert,
+                message: format!("Large transfer of {} wei", value),
+                address: tx["to"].as_str().unwrap_or_default().to_string(),
+                data: tx["hash"].as_str().unwrap_or_default().as_bytes().to_vec(),
+            });
+        }
+    }
+
+    fn handle_block(_block: String) {}
+}
+
+bindings::export!(Component with_types_in bindings);
@@ -0,0 +1,45 @@
+#[allow(warnings)]
+mod bindings;
+
+use bindings::exports::mamoru::agent::handler::Guest;
+use bindings::mamoru::agent::host::{self, Incident, IncidentSeverity};
+use serde_json::Value;
+
+struct Component;
+
+impl Guest for Component {
+    fn handle_transaction(tx: String) {
+        let tx: Value = match serde_json::from_str(&tx) {
+            Ok(tx) => tx,
+            Err(e) => {
+                host::log(&format!("invalid transaction: {}", e));
+                return;
+            }
+        };
+
+        let sender = tx["transaction"]["data"]["sender"]
+            .as_str()
+            .unwrap_or_default();
+        let watched_sender = host::parameter("sender").unwrap_or_default();
+        if !watched_sender.is_empty() && watched_sender != sender {
+            return;
+        }
+
+        let status = &tx["effects"]["status"];
+        if status["status"].as_str() != Some("success") {
+            host::report(&Incident {
+                severity: IncidentSeverity::Warning,
+                message: format!(
+                    "Transaction failed: {}",
+                    status["error"].as_str().unwrap_or("unknown error")
+                ),
+                address: sender.to_string(),
+                data: tx["digest"].as_str().unwrap_or_default().as_bytes().to_vec(),
+            });
+        }
+    }
+
+    fn handle_block(_block: String) {}
+}
+
+bindings::export!(Component with_types_in bindings);